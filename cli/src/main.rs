@@ -5,6 +5,8 @@
 use gumdrop::Options;
 use tracing::log::*;
 
+use std::collections::HashMap;
+
 /*
  * Flags is a structure for managing command linke parameters
  */
@@ -14,6 +16,90 @@ struct Flags {
     help: bool,
     #[options(help = "Table location, can also be set by TABLE_LOCATION")]
     table: Option<String>,
+    #[options(
+        help = "Set the type of a partition column instead of string, e.g. year=integer",
+        meta = "COLUMN=TYPE"
+    )]
+    partition_type: Vec<String>,
+    #[options(help = "Infer the types of partition columns from their values in the paths")]
+    infer_partition_types: bool,
+    #[options(
+        help = "Read partition values from directories laid out like {year}/{month}/{day}",
+        meta = "TEMPLATE"
+    )]
+    partition_template: Option<String>,
+    #[options(
+        help = "Rewrite files with non-microsecond timestamps: disabled, keep or remove the originals",
+        meta = "MODE"
+    )]
+    rewrite_timestamps: Option<String>,
+    #[options(help = "Print the conversion plan as JSON without converting anything")]
+    dry_run: bool,
+    #[options(help = "Add untracked files to and remove missing files from an existing table")]
+    sync: bool,
+    #[options(
+        help = "Print a JSON health report of an existing table, exiting non-zero if it is unhealthy"
+    )]
+    doctor: bool,
+    #[options(
+        help = "Split the conversion into commits of at most this many files",
+        meta = "COUNT"
+    )]
+    max_files_per_commit: Option<usize>,
+    #[options(
+        help = "Write a checkpoint once the table has been created, or of an existing table"
+    )]
+    checkpoint: bool,
+    #[options(
+        help = "Number of commits between checkpoints for tables without delta.checkpointInterval",
+        meta = "COUNT"
+    )]
+    checkpoint_interval: Option<u64>,
+    #[options(
+        help = "Checkpoint an existing table and remove log files older than delta.logRetentionDuration"
+    )]
+    cleanup_logs: bool,
+    #[options(
+        help = "Number of parquet footers to read at the same time",
+        meta = "COUNT"
+    )]
+    footer_concurrency: Option<usize>,
+    #[options(
+        help = "Only convert files matching this glob, relative to the table location",
+        meta = "PATTERN"
+    )]
+    include: Vec<String>,
+    #[options(
+        help = "Ignore files matching this glob, relative to the table location",
+        meta = "PATTERN"
+    )]
+    exclude: Vec<String>,
+    #[options(
+        help = "Treat files with this extension as parquet, instead of only .parquet",
+        meta = "EXT"
+    )]
+    extension: Vec<String>,
+    #[options(help = "Check the magic bytes of files without a parquet extension")]
+    sniff: bool,
+    #[options(
+        help = "Validate files before committing and report, tag or move the invalid ones",
+        meta = "MODE"
+    )]
+    quarantine: Option<String>,
+    #[options(
+        help = "Create the table with this property, e.g. delta.appendOnly=true",
+        meta = "KEY=VALUE"
+    )]
+    property: Vec<String>,
+    #[options(help = "Create the table with this name", meta = "NAME")]
+    table_name: Option<String>,
+    #[options(help = "Create the table with this description", meta = "TEXT")]
+    description: Option<String>,
+    #[options(
+        help = "Record this key in the commitInfo of every commit, e.g. ticket=DATA-42",
+        meta = "KEY=VALUE"
+    )]
+    commit_info: Vec<String>,
 }
 
 /*
@@ -24,6 +110,27 @@ impl Default for Flags {
         Flags {
             help: false,
             table: Some("s3://test-bucket/table".into()),
+            partition_type: vec![],
+            infer_partition_types: false,
+            partition_template: None,
+            rewrite_timestamps: None,
+            dry_run: false,
+            sync: false,
+            doctor: false,
+            max_files_per_commit: None,
+            checkpoint: false,
+            checkpoint_interval: None,
+            cleanup_logs: false,
+            footer_concurrency: None,
+            include: vec![],
+            exclude: vec![],
+            extension: vec![],
+            sniff: false,
+            quarantine: None,
+            property: vec![],
+            table_name: None,
+            description: None,
+            commit_info: vec![],
         }
     }
 }
@@ -41,13 +148,97 @@ async fn main() -> Result<(), anyhow::Error> {
     debug!("Options as read: {:?}", flags);
     let location = table_location(&flags)?;
     info!("Using the table location of: {:?}", location);
+    let options = conversion_options(&flags)?;
+
+    if flags.dry_run {
+        let plan = oxbow::plan(&location, None, &options).await?;
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+        return Ok(());
+    }
+
+    if flags.doctor {
+        let report = oxbow::doctor(&location, None, &options).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if !report.is_healthy() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    oxbow::convert(&location, None)
-        .await
-        .expect("Failed to convert location");
+    if flags.cleanup_logs {
+        let summary = oxbow::cleanup_logs(&location, None, &options).await?;
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    if flags.sync {
+        let summary = oxbow::sync(&location, None, &options).await?;
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    oxbow::convert_with_options(&location, None, &options).await?;
     Ok(())
 }
 
+/*
+ * Build the conversion options for the library from the command line flags
+ */
+fn conversion_options(flags: &Flags) -> Result<oxbow::ConversionOptions, anyhow::Error> {
+    let mut partition_types = HashMap::new();
+    for pair in flags.partition_type.iter() {
+        match pair.split_once('=') {
+            Some((column, type_name)) => {
+                partition_types.insert(column.to_string(), type_name.to_string());
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Partition types must be given as COLUMN=TYPE, not `{pair}`"
+                ))
+            }
+        }
+    }
+
+    let timestamp_rewrite = match &flags.rewrite_timestamps {
+        Some(mode) => mode.parse()?,
+        None => Default::default(),
+    };
+
+    let quarantine = match &flags.quarantine {
+        Some(mode) => mode.parse()?,
+        None => Default::default(),
+    };
+
+    let partition_layout = match &flags.partition_template {
+        Some(template) => template.parse()?,
+        None => Default::default(),
+    };
+
+    Ok(oxbow::ConversionOptions {
+        partition_types,
+        infer_partition_types: flags.infer_partition_types,
+        partition_layout,
+        timestamp_rewrite,
+        max_files_per_commit: flags.max_files_per_commit,
+        checkpoint_after_conversion: flags.checkpoint,
+        checkpoint_interval: flags.checkpoint_interval,
+        footer_concurrency: flags.footer_concurrency,
+        include: flags.include.clone(),
+        exclude: flags.exclude.clone(),
+        parquet_extensions: flags.extension.clone(),
+        sniff_content: flags.sniff,
+        quarantine,
+        table_properties: oxbow::properties::parse_pairs(&flags.property)?,
+        table_name: flags.table_name.clone(),
+        table_description: flags.description.clone(),
+        commit_metadata: oxbow::properties::parse_pairs(&flags.commit_info)?
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect(),
+        ..Default::default()
+    })
+}
+
 /*
  * Return the configured table location. If there is not one configured, this will panic the
  * process..
@@ -72,12 +263,111 @@ mod tests {
 
     #[test]
     fn test_table_location_with_env() {
-        let mut flags = Flags::default();
-        flags.table = None;
+        let flags = Flags {
+            table: None,
+            ..Default::default()
+        };
 
         std::env::set_var("TABLE_LOCATION", "s3://test-bucket-from-env/table");
 
         let location = table_location(&flags).expect("Failed to load table location");
         assert_eq!(location, "s3://test-bucket-from-env/table");
     }
+
+    #[test]
+    fn test_conversion_options_partition_types() {
+        let flags = Flags {
+            partition_type: vec!["year=integer".into(), "ds=date".into()],
+            infer_partition_types: true,
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert!(options.infer_partition_types);
+        assert_eq!(
+            Some(&"integer".to_string()),
+            options.partition_types.get("year")
+        );
+        assert_eq!(Some(&"date".to_string()), options.partition_types.get("ds"));
+    }
+
+    #[test]
+    fn test_conversion_options_rewrite_timestamps() {
+        let flags = Flags {
+            rewrite_timestamps: Some("remove".into()),
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert_eq!(
+            oxbow::rewrite::TimestampRewrite::RemoveOriginals,
+            options.timestamp_rewrite
+        );
+    }
+
+    #[test]
+    fn test_conversion_options_chunked_commits() {
+        let flags = Flags {
+            max_files_per_commit: Some(1000),
+            checkpoint: true,
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert_eq!(Some(1000), options.max_files_per_commit);
+        assert!(options.checkpoint_after_conversion);
+    }
+
+    #[test]
+    fn test_conversion_options_partition_template() {
+        let flags = Flags {
+            partition_template: Some("{year}/{month}/{day}/{hour}".into()),
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert_eq!(Some(4), options.partition_layout.depth());
+
+        let flags = Flags {
+            partition_template: Some("year/month".into()),
+            ..Default::default()
+        };
+        assert!(conversion_options(&flags).is_err());
+    }
+
+    #[test]
+    fn test_conversion_options_table_properties() {
+        let flags = Flags {
+            property: vec!["delta.appendOnly=true".into()],
+            table_name: Some("events".into()),
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert_eq!(
+            Some(&"true".to_string()),
+            options.table_properties.get("delta.appendOnly")
+        );
+        assert_eq!(Some("events".to_string()), options.table_name);
+
+        let flags = Flags {
+            commit_info: vec!["ticket=DATA-42".into()],
+            ..Default::default()
+        };
+        let options = conversion_options(&flags).expect("Failed to build options");
+        assert_eq!(
+            Some(&serde_json::Value::from("DATA-42")),
+            options.commit_metadata.get("ticket")
+        );
+
+        let flags = Flags {
+            property: vec!["delta.appendOnly".into()],
+            ..Default::default()
+        };
+        assert!(conversion_options(&flags).is_err());
+    }
+
+    #[test]
+    fn test_conversion_options_invalid_partition_type() {
+        let flags = Flags {
+            partition_type: vec!["year".into()],
+            ..Default::default()
+        };
+        assert!(conversion_options(&flags).is_err());
+    }
 }
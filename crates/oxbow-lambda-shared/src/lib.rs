@@ -2,17 +2,20 @@
  * oxbow-lambda-shared contains common helper functions and utilities for all oxbow related lambdas
  */
 use aws_lambda_events::s3::{S3Event, S3EventRecord, S3Object};
-use aws_lambda_events::sqs::SqsEvent;
+use aws_lambda_events::sqs::{SqsEvent, SqsMessage};
 use chrono::prelude::*;
 use deltalake::{DeltaResult, ObjectMeta, Path};
+use oxbow::error::OxbowError;
 
 use std::collections::HashMap;
+use std::str::FromStr;
 
 /**
  * Return wholly new [`S3EventRecord`] objects with their the [`S3Object`] `url_decoded_key`
  * properly filled in
  *
- * For whatever reason `aws_lambda_events` does not properly handle this
+ * For whatever reason `aws_lambda_events` does not properly handle this. S3 form-encodes the keys
+ * in notifications, so a `+` is a space and a literal `+` arrives as `%2B`.
  */
 pub fn records_with_url_decoded_keys(records: &[S3EventRecord]) -> Vec<S3EventRecord> {
     use urlencoding::decode;
@@ -26,7 +29,7 @@ pub fn records_with_url_decoded_keys(records: &[S3EventRecord]) -> Vec<S3EventRe
         .map(|record| {
             let mut replacement = record.clone();
             if let Some(key) = &replacement.s3.object.key {
-                if let Ok(decoded_key) = decode(key) {
+                if let Ok(decoded_key) = decode(&key.replace('+', " ")) {
                     replacement.s3.object.url_decoded_key = Some(decoded_key.into_owned());
                 }
             }
@@ -41,6 +44,173 @@ pub fn records_with_url_decoded_keys(records: &[S3EventRecord]) -> Vec<S3EventRe
 pub struct TableMods {
     pub adds: Vec<ObjectMeta>,
     pub removes: Vec<ObjectMeta>,
+    /// Objects which the events may have left in place, to be removed from the table only if they
+    /// no longer exist, see [VersionedDeletes::Check]
+    pub unverified_removes: Vec<ObjectMeta>,
+    /// The time in milliseconds of the latest event which added objects, the sequence of the
+    /// batch's added files
+    pub add_sequence: Option<i64>,
+    /// The time in milliseconds of the latest event which removed objects
+    pub remove_sequence: Option<i64>,
+    /// The ids of the SQS messages whose events make up these modifications
+    pub message_ids: Vec<String>,
+}
+
+impl TableMods {
+    /**
+     * Merge the modifications from another set of events for the same table into these
+     */
+    pub fn merge(&mut self, other: TableMods) {
+        self.adds.extend(other.adds);
+        self.removes.extend(other.removes);
+        self.unverified_removes.extend(other.unverified_removes);
+        self.add_sequence = self.add_sequence.max(other.add_sequence);
+        self.remove_sequence = self.remove_sequence.max(other.remove_sequence);
+        self.message_ids.extend(other.message_ids);
+    }
+}
+
+/// The S3 event types which remove objects from their table unless others are configured: plain
+/// deletes, delete markers in versioned buckets, and lifecycle expirations. Deletes of a specific
+/// version are handled by [VersionedDeletes]
+pub const DEFAULT_REMOVAL_EVENTS: [&str; 2] = ["ObjectRemoved:*", "LifecycleExpiration:*"];
+
+/**
+ * RemovalEvents decides which S3 event types remove objects from their table.
+ *
+ * Each pattern is an event type like `ObjectRemoved:DeleteMarkerCreated`, or a prefix ending in
+ * `*` like `LifecycleExpiration:*`. The `s3:` prefix used when configuring bucket notifications
+ * is optional.
+ */
+#[derive(Debug, Clone)]
+pub struct RemovalEvents {
+    patterns: Vec<String>,
+    versioned_deletes: VersionedDeletes,
+}
+
+/**
+ * VersionedDeletes decides what removal events which permanently delete a specific version of an
+ * object in a versioned bucket, such as an `ObjectRemoved:Delete` or `LifecycleExpiration:Delete`
+ * with a `versionId`, do to the object's table.
+ *
+ * Deleting a noncurrent version leaves the live object in place. Deleting the current version
+ * creates no delete marker, and removes the object unless an older version takes its place. The
+ * event does not say which of these happened.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VersionedDeletes {
+    /// Remove the object from its table only if it no longer exists, by putting it in
+    /// [TableMods::unverified_removes]
+    #[default]
+    Check,
+    /// Always remove the object from its table
+    Remove,
+    /// Never remove the object from its table
+    Ignore,
+}
+
+impl FromStr for VersionedDeletes {
+    type Err = OxbowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "check" => Ok(Self::Check),
+            "remove" => Ok(Self::Remove),
+            "ignore" => Ok(Self::Ignore),
+            other => Err(OxbowError::InvalidConfiguration(format!(
+                "Unknown versioned deletes mode `{other}`, expected one of: check, remove, ignore"
+            ))),
+        }
+    }
+}
+
+/// What a removal event does to its object's table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// The object is gone and is removed from its table
+    Removed,
+    /// The object may still exist, and is removed from its table only once that has been checked
+    Unverified,
+}
+
+impl RemovalEvents {
+    /**
+     * Return the policy for the given patterns, or the [DEFAULT_REMOVAL_EVENTS] if there are none
+     */
+    pub fn new(patterns: &[String]) -> Self {
+        let patterns = match patterns.is_empty() {
+            true => DEFAULT_REMOVAL_EVENTS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            false => patterns.to_vec(),
+        };
+        Self {
+            patterns: patterns
+                .iter()
+                .map(|p| p.trim_start_matches("s3:").to_string())
+                .collect(),
+            versioned_deletes: VersionedDeletes::default(),
+        }
+    }
+
+    /**
+     * Return the policy with the given handling of deletes of a specific object version
+     */
+    pub fn with_versioned_deletes(self, versioned_deletes: VersionedDeletes) -> Self {
+        Self {
+            versioned_deletes,
+            ..self
+        }
+    }
+
+    /**
+     * Return true if the event type removes its object from the table
+     */
+    pub fn matches(&self, event_name: &str) -> bool {
+        let event_name = event_name.trim_start_matches("s3:");
+        self.patterns
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => event_name.starts_with(prefix),
+                None => event_name == pattern,
+            })
+    }
+
+    /**
+     * Return what the event does to its object's table, or `None` if it is not a removal.
+     *
+     * Delete markers hide the current version and `Delete` events without a version, from
+     * unversioned buckets, delete the object itself, so both remove it. A `Delete` event with the
+     * `versionId` it permanently deleted is handled as [VersionedDeletes] configures.
+     */
+    pub fn removal(&self, record: &S3EventRecord) -> Option<Removal> {
+        let event_name = record.event_name.as_ref()?;
+        if !self.matches(event_name) {
+            return None;
+        }
+        let is_versioned = record
+            .s3
+            .object
+            .version_id
+            .as_ref()
+            .map(|version| !version.is_empty() && version != "null")
+            .unwrap_or(false);
+        if event_name.ends_with("DeleteMarkerCreated") || !is_versioned {
+            return Some(Removal::Removed);
+        }
+        match self.versioned_deletes {
+            VersionedDeletes::Check => Some(Removal::Unverified),
+            VersionedDeletes::Remove => Some(Removal::Removed),
+            VersionedDeletes::Ignore => None,
+        }
+    }
+}
+
+impl Default for RemovalEvents {
+    fn default() -> Self {
+        Self::new(&[])
+    }
 }
 
 /**
@@ -52,12 +222,37 @@ pub struct TableMods {
  * appropriate transactions
  */
 pub fn objects_by_table(records: &[S3EventRecord]) -> HashMap<String, TableMods> {
+    objects_by_table_matching(records, None, &RemovalEvents::default(), |_| true)
+}
+
+/**
+ * Group the objects from the notification based on the delta tables they should be added to,
+ * ignoring any object whose path relative to its table is rejected by `matches`
+ *
+ * See [infer_log_path_with] for how `template_depth` locates the tables. Objects are removed from
+ * their table by the event types which `removals` matches.
+ */
+pub fn objects_by_table_matching<F>(
+    records: &[S3EventRecord],
+    template_depth: Option<usize>,
+    removals: &RemovalEvents,
+    matches: F,
+) -> HashMap<String, TableMods>
+where
+    F: Fn(&Path) -> bool,
+{
     let mut mods = HashMap::new();
 
     for record in records.iter() {
         if let Some(bucket) = &record.s3.bucket.name {
-            let log_path = infer_log_path_from(record.s3.object.url_decoded_key.as_ref().unwrap());
+            let log_path = infer_log_path_with(
+                record.s3.object.url_decoded_key.as_ref().unwrap(),
+                template_depth,
+            );
             let om = into_object_meta(&record.s3.object, Some(&log_path));
+            if !matches(&om.location) {
+                continue;
+            }
 
             let key = format!("s3://{}/{}", bucket, log_path);
 
@@ -65,11 +260,21 @@ pub fn objects_by_table(records: &[S3EventRecord]) -> HashMap<String, TableMods>
                 mods.insert(key.clone(), TableMods::default());
             }
             if let Some(objects) = mods.get_mut(&key) {
+                let sequence = Some(record.event_time.timestamp_millis());
                 if let Some(event_name) = &record.event_name {
                     if event_name.starts_with("ObjectCreated") {
+                        objects.add_sequence = objects.add_sequence.max(sequence);
                         objects.adds.push(om);
-                    } else if event_name == "ObjectRemoved:Delete" {
-                        objects.removes.push(om);
+                    } else {
+                        let removal = removals.removal(record);
+                        if removal.is_some() {
+                            objects.remove_sequence = objects.remove_sequence.max(sequence);
+                        }
+                        match removal {
+                            Some(Removal::Removed) => objects.removes.push(om),
+                            Some(Removal::Unverified) => objects.unverified_removes.push(om),
+                            None => {}
+                        }
                     }
                 }
             }
@@ -79,6 +284,37 @@ pub fn objects_by_table(records: &[S3EventRecord]) -> HashMap<String, TableMods>
     mods
 }
 
+/**
+ * Group the objects from every message of the SQS event by the tables they belong to, like
+ * [objects_by_table_matching] does.
+ *
+ * Each table has a [TableMods] for every message with events for it, in the order the messages
+ * were received and with the message's id in [TableMods::message_ids]. Keeping the messages apart
+ * lets those which have already been applied to a table be left out when they are redelivered,
+ * and the rest can be combined with [TableMods::merge].
+ */
+pub fn objects_by_table_from_sqs<F>(
+    event: &SqsEvent,
+    template_depth: Option<usize>,
+    removals: &RemovalEvents,
+    matches: F,
+) -> DeltaResult<HashMap<String, Vec<TableMods>>>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut by_table: HashMap<String, Vec<TableMods>> = HashMap::new();
+    for message in event.records.iter() {
+        let records = records_with_url_decoded_keys(&s3_from_sqs_message(message)?);
+        for (table, mut mods) in
+            objects_by_table_matching(&records, template_depth, removals, &matches)
+        {
+            mods.message_ids.extend(message.message_id.clone());
+            by_table.entry(table).or_default().push(mods);
+        }
+    }
+    Ok(by_table)
+}
+
 /**
  * Infer the log path from the given object path.
  *
@@ -87,8 +323,33 @@ pub fn objects_by_table(records: &[S3EventRecord]) -> HashMap<String, TableMods>
  * `add` actions in the log can use relative file paths for newly added objects
  */
 pub fn infer_log_path_from(path: &str) -> String {
+    infer_log_path_with(path, None)
+}
+
+/**
+ * Infer the log path from the given object path, where `template_depth` is the number of
+ * directories of a partition path template between the table and its objects, e.g. 4 for
+ * `{year}/{month}/{day}/{hour}`. Without one the root is found by looking for hive-style
+ * partitions.
+ *
+ * The root never extends past a hidden directory such as `_quarantine` or `.spark-staging-1`, so
+ * objects beneath one stay part of the table they were written into rather than becoming a table
+ * of their own.
+ */
+pub fn infer_log_path_with(path: &str, template_depth: Option<usize>) -> String {
     use std::path::{Component, Path};
 
+    if let Some(depth) = template_depth {
+        let directories: Vec<&str> = path.split('/').collect();
+        let directories = &directories[..directories.len() - 1];
+        return directories[..directories.len().saturating_sub(depth)]
+            .iter()
+            .take_while(|segment| !is_hidden_segment(segment))
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/");
+    }
+
     let mut root = vec![];
 
     for component in Path::new(path)
@@ -102,7 +363,7 @@ pub fn infer_log_path_from(path: &str) -> String {
                  * If a segment has what looks like a hive-style partition, bail and call that the root of
                  * the delta table
                  */
-                if segment.find('=') >= Some(0) {
+                if segment.find('=') >= Some(0) || is_hidden_segment(segment) {
                     break;
                 }
                 root.push(segment);
@@ -112,6 +373,14 @@ pub fn infer_log_path_from(path: &str) -> String {
     root.join("/")
 }
 
+/**
+ * Return true if the directory is hidden by Hadoop's conventions, e.g. `_temporary` or
+ * `.spark-staging-1`, like `oxbow::spark::is_hidden` decides for each segment of a path
+ */
+fn is_hidden_segment(segment: &str) -> bool {
+    segment.starts_with('.') || (segment.starts_with('_') && !segment.contains('='))
+}
+
 /// A simple structure to make deserializing test events for identification easier
 ///
 /// See <fhttps://github.com/buoyant-data/oxbow/issues/8>
@@ -130,34 +399,37 @@ struct TestEvent {
 ///  errorsin the processing pipeline
 pub fn s3_from_sqs(event: SqsEvent) -> DeltaResult<Vec<S3EventRecord>> {
     let mut records = vec![];
-    for record in event.records.iter() {
-        /* each record is an SqsMessage */
-        if let Some(body) = &record.body {
-            match serde_json::from_str::<S3Event>(body) {
-                Ok(s3event) => {
-                    for s3record in s3event.records {
-                        records.push(s3record.clone());
-                    }
-                }
-                Err(err) => {
-                    // if we cannot deserialize and the event is an s3::TestEvent, then we should
-                    // just return empty records.
-                    let test_event = serde_json::from_str::<TestEvent>(body);
-                    // Early exit with the original error if we cannot parse the JSON at all
-                    if test_event.is_err() {
-                        return Err(err.into());
-                    }
+    for message in event.records.iter() {
+        records.append(&mut s3_from_sqs_message(message)?);
+    }
+    Ok(records)
+}
 
-                    // Ignore the error on deserialization if the event ends up being an S3
-                    // TestEvent which is fired when bucket notifications are originally configured
-                    if "s3:TestEvent" != test_event.unwrap().event {
-                        return Err(err.into());
-                    }
-                }
-            };
+/// Convert a single [aws_lambda_events::sqs::SqsMessage] to the
+///  [aws_lambda_events::s3::S3EventRecord] entities in its body, see [s3_from_sqs]
+pub fn s3_from_sqs_message(message: &SqsMessage) -> DeltaResult<Vec<S3EventRecord>> {
+    let Some(body) = &message.body else {
+        return Ok(vec![]);
+    };
+    match serde_json::from_str::<S3Event>(body) {
+        Ok(s3event) => Ok(s3event.records),
+        Err(err) => {
+            // if we cannot deserialize and the event is an s3::TestEvent, then we should
+            // just return empty records.
+            let test_event = serde_json::from_str::<TestEvent>(body);
+            // Early exit with the original error if we cannot parse the JSON at all
+            if test_event.is_err() {
+                return Err(err.into());
+            }
+
+            // Ignore the error on deserialization if the event ends up being an S3
+            // TestEvent which is fired when bucket notifications are originally configured
+            if "s3:TestEvent" != test_event.unwrap().event {
+                return Err(err.into());
+            }
+            Ok(vec![])
         }
     }
-    Ok(records)
 }
 
 /**
@@ -170,9 +442,15 @@ fn into_object_meta(s3object: &S3Object, prune_prefix: Option<&str>) -> ObjectMe
     let location = s3object.url_decoded_key.clone().unwrap_or("".to_string());
 
     let location = match prune_prefix {
-        Some(prune) => Path::from(location.strip_prefix(prune).unwrap_or(&location)),
-        None => Path::from(location),
+        Some(prune) => location
+            .strip_prefix(prune)
+            .unwrap_or(&location)
+            .trim_start_matches('/')
+            .to_string(),
+        None => location,
     };
+    // Keys with escaped hive partitions contain `%`, which must not be encoded a second time
+    let location = Path::parse(&location).unwrap_or_else(|_| Path::from(location));
     ObjectMeta {
         size: s3object.size.unwrap_or(0) as usize,
         last_modified: Utc::now(),
@@ -239,6 +517,37 @@ mod tests {
         assert_eq!(expected.size, result.size);
     }
 
+    #[test]
+    fn into_object_meta_with_escaped_partition() {
+        let s3object = S3Object {
+            key: Some("table/c2%3D%2B+%253D%25250/part-00000.c000.snappy.parquet".into()),
+            size: Some(1024),
+            url_decoded_key: None,
+            version_id: None,
+            e_tag: None,
+            sequencer: None,
+        };
+        let record = S3EventRecord {
+            s3: aws_lambda_events::s3::S3Entity {
+                object: s3object,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let records = records_with_url_decoded_keys(&[record]);
+        let key = records[0].s3.object.url_decoded_key.clone();
+        assert_eq!(
+            Some("table/c2=+ %3D%250/part-00000.c000.snappy.parquet"),
+            key.as_deref()
+        );
+
+        let result = into_object_meta(&records[0].s3.object, Some("table"));
+        assert_eq!(
+            "c2=+ %3D%250/part-00000.c000.snappy.parquet",
+            result.location.as_ref()
+        );
+    }
+
     /**
      * It is valid to have a bucket totally dedicated to the delta table such that there is no
      * prefix
@@ -250,6 +559,36 @@ mod tests {
         assert_eq!(expected, infer_log_path_from(object));
     }
 
+    #[test]
+    fn infer_log_path_with_template() {
+        let object = "firehose/events/2023/10/17/13/events-1-2023-10-17-13-00-00.parquet";
+        assert_eq!("firehose/events", infer_log_path_with(object, Some(4)));
+        assert_eq!("", infer_log_path_with("2023/10/17/a.parquet", Some(4)));
+        assert_eq!(
+            "firehose/events/2023/10/17/13",
+            infer_log_path_with(object, None)
+        );
+    }
+
+    #[test]
+    fn infer_log_path_stops_at_hidden_directories() {
+        assert_eq!(
+            "tables/events",
+            infer_log_path_from("tables/events/_quarantine/part-0.parquet")
+        );
+        assert_eq!(
+            "tables/events",
+            infer_log_path_from("tables/events/.spark-staging-1/ds=1/part-0.parquet")
+        );
+        assert_eq!(
+            "firehose/events",
+            infer_log_path_with(
+                "firehose/events/_temporary/2023/10/17/13/part-0.parquet",
+                Some(4)
+            )
+        );
+    }
+
     #[test]
     fn infer_log_path_from_hive_partitioned_object() {
         let object = "some/path/ds=2023-05-05/site=delta.io/beta.parquet";
@@ -343,6 +682,226 @@ mod tests {
         );
     }
 
+    #[test]
+    fn group_objects_to_tables_matching() {
+        let buf = std::fs::read_to_string("../../tests/data/s3-event-multiple.json")
+            .expect("Failed to read file");
+        let event: S3Event = serde_json::from_str(&buf).expect("Failed to parse");
+        let records = records_with_url_decoded_keys(&event.records);
+
+        let groupings =
+            objects_by_table_matching(&records, None, &RemovalEvents::default(), |_| false);
+        assert!(
+            groupings.is_empty(),
+            "Every object should have been filtered"
+        );
+
+        // Paths are matched relative to the table they belong to
+        let groupings =
+            objects_by_table_matching(&records, None, &RemovalEvents::default(), |path| {
+                path.as_ref() != "a.parquet"
+            });
+        assert_eq!(2, groupings.len());
+        let table_two = groupings
+            .get("s3://example-bucket/some/prefix")
+            .expect("Failed to get the second table");
+        assert_eq!(1, table_two.adds.len());
+    }
+
+    #[test]
+    fn removal_event_types() {
+        let defaults = RemovalEvents::default();
+        for event_name in [
+            "ObjectRemoved:Delete",
+            "ObjectRemoved:DeleteMarkerCreated",
+            "LifecycleExpiration:Delete",
+            "LifecycleExpiration:DeleteMarkerCreated",
+        ] {
+            assert!(defaults.matches(event_name), "{event_name} is a removal");
+        }
+        assert!(!defaults.matches("ObjectCreated:Put"));
+        assert!(!defaults.matches("LifecycleTransition"));
+
+        let configured = RemovalEvents::new(&["s3:ObjectRemoved:Delete".into()]);
+        assert!(configured.matches("ObjectRemoved:Delete"));
+        assert!(!configured.matches("ObjectRemoved:DeleteMarkerCreated"));
+        assert!(!configured.matches("LifecycleExpiration:Delete"));
+    }
+
+    #[test]
+    fn group_delete_markers_as_removals() {
+        let record = |event_name: &str| S3EventRecord {
+            event_name: Some(event_name.into()),
+            s3: aws_lambda_events::s3::S3Entity {
+                bucket: aws_lambda_events::s3::S3Bucket {
+                    name: Some("example-bucket".into()),
+                    ..Default::default()
+                },
+                object: S3Object {
+                    url_decoded_key: Some(format!("table/{event_name}.parquet")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let records = vec![
+            record("ObjectRemoved:DeleteMarkerCreated"),
+            record("LifecycleExpiration:Delete"),
+            record("LifecycleTransition"),
+        ];
+
+        let groupings = objects_by_table(&records);
+        let table = groupings
+            .get("s3://example-bucket/table")
+            .expect("Failed to get the table");
+        assert_eq!(2, table.removes.len());
+        assert!(table.adds.is_empty());
+    }
+
+    #[test]
+    fn versioned_deletes_by_policy() {
+        let record = |event_name: &str, version_id: Option<&str>| S3EventRecord {
+            event_name: Some(event_name.into()),
+            s3: aws_lambda_events::s3::S3Entity {
+                object: S3Object {
+                    version_id: version_id.map(|v| v.into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let defaults = RemovalEvents::default();
+        let removed = Some(Removal::Removed);
+
+        // Unversioned buckets have no versions to delete, the object itself is gone
+        assert_eq!(
+            removed,
+            defaults.removal(&record("ObjectRemoved:Delete", None))
+        );
+        assert_eq!(
+            removed,
+            defaults.removal(&record("ObjectRemoved:Delete", Some("null")))
+        );
+        assert_eq!(
+            removed,
+            defaults.removal(&record("LifecycleExpiration:Delete", None))
+        );
+
+        // Delete markers hide the current version
+        let version = Some("3HL4kqtJlcpXroDTDmJ");
+        assert_eq!(
+            removed,
+            defaults.removal(&record("ObjectRemoved:DeleteMarkerCreated", version))
+        );
+        assert_eq!(
+            removed,
+            defaults.removal(&record("LifecycleExpiration:DeleteMarkerCreated", version))
+        );
+
+        // Permanently deleting or expiring a specific version may or may not leave the object
+        let unverified = Some(Removal::Unverified);
+        assert_eq!(
+            unverified,
+            defaults.removal(&record("ObjectRemoved:Delete", version))
+        );
+        assert_eq!(
+            unverified,
+            defaults.removal(&record("LifecycleExpiration:Delete", version))
+        );
+
+        let removing = RemovalEvents::default().with_versioned_deletes(VersionedDeletes::Remove);
+        assert_eq!(
+            removed,
+            removing.removal(&record("LifecycleExpiration:Delete", version))
+        );
+        let ignoring = RemovalEvents::new(&["ObjectRemoved:Delete".into()])
+            .with_versioned_deletes(VersionedDeletes::Ignore);
+        assert_eq!(
+            None,
+            ignoring.removal(&record("ObjectRemoved:Delete", version))
+        );
+        assert_eq!(
+            removed,
+            ignoring.removal(&record("ObjectRemoved:Delete", None))
+        );
+    }
+
+    #[test]
+    fn versioned_deletes_modes() {
+        assert_eq!(
+            VersionedDeletes::Check,
+            "check".parse().expect("Failed to parse")
+        );
+        assert_eq!(
+            VersionedDeletes::Ignore,
+            "IGNORE".parse().expect("Failed to parse")
+        );
+        assert!(matches!(
+            "sometimes".parse::<VersionedDeletes>(),
+            Err(OxbowError::InvalidConfiguration(_))
+        ));
+    }
+
+    #[test]
+    fn group_versioned_deletes() {
+        let record = |key: &str, event_name: &str, version_id: Option<&str>| S3EventRecord {
+            event_name: Some(event_name.into()),
+            s3: aws_lambda_events::s3::S3Entity {
+                bucket: aws_lambda_events::s3::S3Bucket {
+                    name: Some("example-bucket".into()),
+                    ..Default::default()
+                },
+                object: S3Object {
+                    url_decoded_key: Some(format!("table/{key}.parquet")),
+                    version_id: version_id.map(|v| v.into()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let records = vec![
+            record("marked", "ObjectRemoved:DeleteMarkerCreated", Some("v2")),
+            record("only-version", "ObjectRemoved:Delete", Some("v1")),
+            record("noncurrent", "LifecycleExpiration:Delete", Some("v1")),
+        ];
+        let locations = |objects: &[ObjectMeta]| {
+            objects
+                .iter()
+                .map(|o| o.location.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let groupings = objects_by_table(&records);
+        let table = groupings
+            .get("s3://example-bucket/table")
+            .expect("Failed to get the table");
+        assert_eq!(
+            vec![Path::from("marked.parquet")],
+            locations(&table.removes)
+        );
+        assert_eq!(
+            vec![
+                Path::from("only-version.parquet"),
+                Path::from("noncurrent.parquet")
+            ],
+            locations(&table.unverified_removes)
+        );
+
+        let removals = RemovalEvents::default().with_versioned_deletes(VersionedDeletes::Ignore);
+        let groupings = objects_by_table_matching(&records, None, &removals, |_| true);
+        let table = groupings
+            .get("s3://example-bucket/table")
+            .expect("Failed to get the table");
+        assert_eq!(
+            vec![Path::from("marked.parquet")],
+            locations(&table.removes)
+        );
+        assert!(table.unverified_removes.is_empty());
+    }
+
     #[test]
     fn test_s3_from_sqs() {
         let buf = std::fs::read_to_string("../../tests/data/s3-event-multiple.json")
@@ -382,7 +941,7 @@ mod tests {
                 "Should have recorded a removes table modification"
             );
         } else {
-            assert!(false, "Failed to find the right key on {tables:?}");
+            panic!("Failed to find the right key on {tables:?}");
         }
     }
 
@@ -421,4 +980,57 @@ mod tests {
             "Should have treated a test event like a no-op: {response:?}"
         );
     }
+
+    #[test]
+    fn test_message_ids_by_table() {
+        let body = |key: &str| {
+            format!(
+                r#"{{"Records":[{{"eventVersion":"2.1","eventSource":"aws:s3","awsRegion":"us-west-2","eventTime":"2023-12-18T00:22:24.292Z","eventName":"ObjectCreated:Put","userIdentity":{{"principalId":"A16S3A764ZBGJN"}},"requestParameters":{{"sourceIPAddress":"76.218.225.124"}},"responseElements":{{}},"s3":{{"s3SchemaVersion":"1.0","configurationId":"test","bucket":{{"name":"oxbow-simple","ownerIdentity":{{"principalId":"A16S3A764ZBGJN"}},"arn":"arn:aws:s3:::oxbow-simple"}},"object":{{"key":"{key}","size":10,"sequencer":"00657F90C047858AE9"}}}}}}]}}"#
+            )
+        };
+        let message = |id: &str, key: &str| SqsMessage {
+            message_id: Some(id.into()),
+            body: Some(body(key)),
+            ..Default::default()
+        };
+        let event = SqsEvent {
+            records: vec![
+                message("first", "left/a.parquet"),
+                message("second", "right/b.parquet"),
+                message("third", "left/c.parquet"),
+            ],
+        };
+
+        let tables = objects_by_table_from_sqs(&event, None, &RemovalEvents::default(), |_| true)
+            .expect("Failed to group the messages");
+        let left = tables
+            .get("s3://oxbow-simple/left")
+            .expect("Failed to get the left table");
+        assert_eq!(
+            vec![vec!["first".to_string()], vec!["third".to_string()]],
+            left.iter()
+                .map(|mods| mods.message_ids.clone())
+                .collect::<Vec<_>>()
+        );
+        assert!(left.iter().all(|mods| mods.adds.len() == 1));
+
+        let mut merged = TableMods::default();
+        for mods in left.iter().cloned() {
+            merged.merge(mods);
+        }
+        assert_eq!(2, merged.adds.len());
+        assert_eq!(
+            vec!["first".to_string(), "third".to_string()],
+            merged.message_ids
+        );
+        // The batch is sequenced by the time of its latest event
+        assert_eq!(Some(1702858944292), merged.add_sequence);
+        assert_eq!(None, merged.remove_sequence);
+
+        let right = tables
+            .get("s3://oxbow-simple/right")
+            .expect("Failed to get the right table");
+        assert_eq!(1, right.len());
+        assert_eq!(vec!["second".to_string()], right[0].message_ids);
+    }
 }
@@ -0,0 +1,272 @@
+/*
+ * The checkpoint module decides when oxbow writes checkpoints of the tables it commits to, and
+ * removes the log files which those checkpoints have made redundant
+ */
+use chrono::prelude::*;
+use deltalake::{DeltaConfigKey, DeltaTable, ObjectMeta, ObjectStore, Path};
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::log::*;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::error::OxbowResult;
+use crate::properties;
+use crate::ConversionOptions;
+
+/// The number of commits between checkpoints when neither the table nor the options set one
+pub const DEFAULT_INTERVAL: u64 = 10;
+
+/// How long log files are kept for tables without a `delta.logRetentionDuration`
+pub const DEFAULT_LOG_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// What writing a checkpoint did
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckpointSummary {
+    /// The version of the table the checkpoint was written for
+    pub version: i64,
+    /// The number of expired log files which were removed after the checkpoint
+    pub deleted_log_files: usize,
+}
+
+/**
+ * Return the number of commits between checkpoints of the table.
+ *
+ * The table's `delta.checkpointInterval` property is used when it is set, otherwise
+ * [ConversionOptions::checkpoint_interval] or [DEFAULT_INTERVAL].
+ */
+pub fn interval_for(table: &DeltaTable, options: &ConversionOptions) -> u64 {
+    table
+        .get_metadata()
+        .ok()
+        .and_then(|metadata| {
+            metadata
+                .configuration
+                .get(DeltaConfigKey::CheckpointInterval.as_ref())
+                .cloned()
+                .flatten()
+        })
+        .and_then(|interval| interval.parse().ok())
+        .or(options.checkpoint_interval)
+        .filter(|interval| *interval > 0)
+        .unwrap_or(DEFAULT_INTERVAL)
+}
+
+/**
+ * Return true if a checkpoint should be written at `version`
+ */
+// `is_multiple_of` needs a newer toolchain than oxbow otherwise builds with
+#[allow(clippy::manual_is_multiple_of)]
+pub fn is_due(version: i64, interval: u64) -> bool {
+    version > 0 && interval > 0 && version as u64 % interval == 0
+}
+
+/**
+ * Write a checkpoint at `version` if one is due, returning true if one was written.
+ *
+ * Other writers may have committed since `version`, so the checkpoint is always of `version`
+ * itself rather than of whatever the latest version is. The table is left at its latest version.
+ */
+pub async fn checkpoint_if_due(
+    table: &mut DeltaTable,
+    version: i64,
+    options: &ConversionOptions,
+) -> OxbowResult<bool> {
+    let interval = interval_for(table, options);
+    if !is_due(version, interval) {
+        return Ok(false);
+    }
+    checkpoint_version(table, version, options).await?;
+    Ok(true)
+}
+
+/**
+ * Write a checkpoint after committing `version` if one is due. The commit has already succeeded,
+ * so a failure to write the checkpoint is only logged and left for the next one.
+ */
+pub(crate) async fn checkpoint_after_commit(
+    table: &mut DeltaTable,
+    version: i64,
+    options: &ConversionOptions,
+) {
+    if let Err(err) = checkpoint_if_due(table, version, options).await {
+        error!(
+            "Failed to create a checkpoint of {} at version {version}: {err:?}",
+            table.table_uri()
+        );
+    }
+}
+
+/**
+ * Write a checkpoint of the table at `version`, leaving the table at its latest version.
+ *
+ * With [ConversionOptions::expired_log_cleanup] the log files which the checkpoint has made
+ * redundant are then removed, see [cleanup_expired_logs].
+ */
+pub async fn checkpoint_version(
+    table: &mut DeltaTable,
+    version: i64,
+    options: &ConversionOptions,
+) -> OxbowResult<CheckpointSummary> {
+    if table.version() != version {
+        table.load_version(version).await?;
+    }
+    info!(
+        "Creating a checkpoint of {} at version {version}",
+        table.table_uri()
+    );
+    deltalake::checkpoints::create_checkpoint(table).await?;
+
+    let deleted_log_files = match options.expired_log_cleanup {
+        true => cleanup_expired_logs(table).await?,
+        false => 0,
+    };
+    table.update().await?;
+    Ok(CheckpointSummary {
+        version,
+        deleted_log_files,
+    })
+}
+
+/**
+ * Return how long the table's log files are kept, from its `delta.logRetentionDuration`
+ */
+pub fn log_retention_for(table: &DeltaTable) -> OxbowResult<Duration> {
+    let retention = table.get_metadata().ok().and_then(|metadata| {
+        metadata
+            .configuration
+            .get(DeltaConfigKey::LogRetentionDuration.as_ref())
+            .cloned()
+            .flatten()
+    });
+    match retention {
+        Some(retention) => properties::parse_interval(&retention),
+        None => Ok(DEFAULT_LOG_RETENTION),
+    }
+}
+
+/**
+ * Remove the log files which are older than the table's `delta.logRetentionDuration`, returning
+ * how many were removed.
+ *
+ * Readers need a checkpoint to start from once the commits before it are gone, so only the
+ * versions before the newest checkpoint which has itself expired are removed, and nothing is
+ * removed when there is no such checkpoint. Those versions are removed oldest first, up to the
+ * first one which has not expired. Tables with `delta.enableExpiredLogCleanup` set to false are
+ * left alone.
+ */
+pub async fn cleanup_expired_logs(table: &DeltaTable) -> OxbowResult<usize> {
+    if !table.get_state().enable_expired_log_cleanup() {
+        debug!(
+            "Expired log cleanup is disabled for {}, skipping",
+            table.table_uri()
+        );
+        return Ok(0);
+    }
+    let retention = chrono::Duration::from_std(log_retention_for(table)?).ok();
+    let Some(cutoff) = retention.and_then(|r| Utc::now().checked_sub_signed(r)) else {
+        return Ok(0);
+    };
+
+    let store = table.object_store();
+    let mut versions: BTreeMap<i64, Vec<ObjectMeta>> = BTreeMap::new();
+    let mut listing = store.list(Some(&Path::from("_delta_log"))).await?;
+    while let Some(file) = listing.next().await {
+        let file = file?;
+        if let Some(version) = log_version_of(&file.location) {
+            if version <= table.version() {
+                versions.entry(version).or_default().push(file);
+            }
+        }
+    }
+
+    let expired_checkpoint = versions.iter().rev().find_map(|(version, files)| {
+        let checkpoints: Vec<&ObjectMeta> = files.iter().filter(|f| is_checkpoint(f)).collect();
+        let expired =
+            !checkpoints.is_empty() && checkpoints.iter().all(|f| f.last_modified <= cutoff);
+        expired.then_some(*version)
+    });
+    let Some(checkpoint) = expired_checkpoint else {
+        debug!(
+            "{} has no expired checkpoint, no log files can be removed",
+            table.table_uri()
+        );
+        return Ok(0);
+    };
+
+    let mut deleted = 0;
+    for files in versions.range(..checkpoint).map(|(_, files)| files) {
+        if files.iter().any(|f| f.last_modified > cutoff) {
+            break;
+        }
+        for file in files.iter() {
+            store.delete(&file.location).await?;
+            deleted += 1;
+        }
+    }
+    info!(
+        "Removed {deleted} expired log files from {} before the checkpoint at version {checkpoint}",
+        table.table_uri()
+    );
+    Ok(deleted)
+}
+
+fn is_checkpoint(file: &ObjectMeta) -> bool {
+    file.location
+        .filename()
+        .map(|name| name.contains(".checkpoint."))
+        .unwrap_or(false)
+}
+
+/**
+ * Return the version of a commit or checkpoint file in the log, or `None` for any other file
+ */
+fn log_version_of(location: &Path) -> Option<i64> {
+    let name = location.filename()?;
+    let (version, suffix) = (name.get(..20)?, name.get(20..)?);
+    if !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if suffix == ".json" || suffix.starts_with(".checkpoint.") {
+        version.parse().ok()
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoints_are_due_on_the_interval() {
+        assert!(!is_due(0, 10));
+        assert!(!is_due(9, 10));
+        assert!(is_due(10, 10));
+        assert!(is_due(20, 10));
+        assert!(is_due(3, 1));
+        assert!(!is_due(3, 0));
+    }
+
+    #[test]
+    fn log_versions() {
+        for (path, expected) in [
+            ("_delta_log/00000000000000000007.json", Some(7)),
+            (
+                "_delta_log/00000000000000000010.checkpoint.parquet",
+                Some(10),
+            ),
+            (
+                "_delta_log/00000000000000000010.checkpoint.0000000001.0000000002.parquet",
+                Some(10),
+            ),
+            ("_delta_log/_last_checkpoint", None),
+            ("_delta_log/00000000000000000007.crc", None),
+            ("_delta_log/_commit_abc.json.tmp", None),
+        ] {
+            assert_eq!(expected, log_version_of(&Path::from(path)), "{path}");
+        }
+    }
+}
@@ -0,0 +1,260 @@
+/*
+ * The doctor module contains a read-only health check of a Delta table against the files which
+ * are actually in its location
+ */
+use deltalake::{DeltaTable, ObjectMeta, ObjectStore, Path};
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::log::*;
+
+use std::collections::HashMap;
+
+use crate::error::OxbowResult;
+use crate::partitions::PartitionLayout;
+use crate::ConversionOptions;
+
+/// The problems found with a table, serializable to JSON for alerting
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HealthReport {
+    /// The version of the table which was checked
+    pub version: i64,
+    /// The number of files the table tracks
+    pub tracked_files: usize,
+    /// Files the table tracks which do not exist in storage
+    pub missing: Vec<String>,
+    /// Files whose size in storage differs from the size in the log
+    pub size_mismatches: Vec<SizeMismatch>,
+    /// Parquet files in storage which the table does not track
+    pub untracked: Vec<String>,
+    /// Files whose partition path disagrees with the partition values in the log
+    pub partition_mismatches: Vec<PartitionMismatch>,
+    /// Files which cannot be read, or whose schema is not compatible with the table
+    pub incompatible: Vec<IncompatibleFile>,
+}
+
+impl HealthReport {
+    /// Return true if no problems were found
+    pub fn is_healthy(&self) -> bool {
+        self.missing.is_empty()
+            && self.size_mismatches.is_empty()
+            && self.untracked.is_empty()
+            && self.partition_mismatches.is_empty()
+            && self.incompatible.is_empty()
+    }
+}
+
+/// A file whose size in storage differs from the log
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeMismatch {
+    pub path: String,
+    pub log_size: i64,
+    pub storage_size: usize,
+}
+
+/// A file whose partition path disagrees with its `partitionValues`
+#[derive(Debug, Clone, Serialize)]
+pub struct PartitionMismatch {
+    pub path: String,
+    pub path_values: HashMap<String, Option<String>>,
+    pub log_values: HashMap<String, Option<String>>,
+}
+
+/// A file which cannot be read or is not compatible with the table schema
+#[derive(Debug, Clone, Serialize)]
+pub struct IncompatibleFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/**
+ * Check the health of the table without modifying it.
+ *
+ * Untracked files are found with the same discovery rules and filters as a conversion, and the
+ * footers of every tracked and untracked file are read to check them against the table schema.
+ */
+pub async fn check_table(
+    table: &DeltaTable,
+    options: &ConversionOptions,
+) -> OxbowResult<HealthReport> {
+    let store = table.object_store();
+    let metadata = table.get_metadata()?;
+    let mut report = HealthReport {
+        version: table.version(),
+        tracked_files: table.get_state().files().len(),
+        ..Default::default()
+    };
+
+    let mut stored: HashMap<Path, ObjectMeta> = HashMap::new();
+    let mut iter = store.list(None).await?;
+    while let Some(meta) = iter.next().await {
+        let meta = meta?;
+        stored.insert(meta.location.clone(), meta);
+    }
+    drop(iter);
+
+    let mut present = vec![];
+    for add in table.get_state().files().iter() {
+        let location = Path::parse(&add.path).unwrap_or_else(|_| Path::from(add.path.as_ref()));
+
+        if let Some(mismatch) = partition_mismatch(
+            &location,
+            &add.partition_values,
+            &metadata.partition_columns,
+            &options.partition_layout,
+        ) {
+            report.partition_mismatches.push(mismatch);
+        }
+
+        match stored.get(&location) {
+            None => report.missing.push(location.to_string()),
+            Some(meta) => {
+                if meta.size as i64 != add.size {
+                    report.size_mismatches.push(SizeMismatch {
+                        path: location.to_string(),
+                        log_size: add.size,
+                        storage_size: meta.size,
+                    });
+                }
+                present.push(meta.clone());
+            }
+        }
+    }
+
+    let tracked = table.get_file_set();
+    let untracked: Vec<ObjectMeta> =
+        crate::discover_parquet_files_with_options(store.clone(), options)
+            .await?
+            .into_iter()
+            .filter(|f| !tracked.contains(&f.location))
+            .collect();
+    report.untracked = untracked.iter().map(|f| f.location.to_string()).collect();
+    present.extend(untracked);
+
+    let footers = options.footer_cache(store);
+    let validated =
+        crate::validate::validate_files(&present, &footers, Some(&metadata.schema)).await;
+    report.incompatible = validated
+        .invalid
+        .into_iter()
+        .chain(validated.incompatible)
+        .map(|invalid| IncompatibleFile {
+            path: invalid.file.location.to_string(),
+            reason: invalid.reason,
+        })
+        .collect();
+
+    if !report.is_healthy() {
+        warn!("The table at version {} is not healthy", report.version);
+    }
+    Ok(report)
+}
+
+/**
+ * Compare the partition values encoded in the path with those recorded in the log
+ */
+fn partition_mismatch(
+    location: &Path,
+    log_values: &HashMap<String, Option<String>>,
+    partition_columns: &[String],
+    layout: &PartitionLayout,
+) -> Option<PartitionMismatch> {
+    let path_values: HashMap<String, Option<String>> = layout
+        .partition_values_from(location.as_ref())
+        .into_iter()
+        .filter(|(key, _)| partition_columns.contains(key))
+        .collect();
+
+    let agrees = partition_columns.iter().all(|column| {
+        match (path_values.get(column), log_values.get(column)) {
+            (Some(path), Some(log)) => path == log,
+            // A partition missing from the path must be null in the log
+            (None, Some(log)) => log.is_none(),
+            (None, None) => true,
+            (Some(path), None) => path.is_none(),
+        }
+    });
+
+    (!agrees).then(|| PartitionMismatch {
+        path: location.to_string(),
+        path_values,
+        log_values: log_values.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partition_values_agree() {
+        let columns = vec!["ds".to_string()];
+        let values = HashMap::from([("ds".to_string(), Some("2023-01-01".to_string()))]);
+        assert!(partition_mismatch(
+            &Path::from("ds=2023-01-01/a.parquet"),
+            &values,
+            &columns,
+            &PartitionLayout::Hive
+        )
+        .is_none());
+
+        let mismatch = partition_mismatch(
+            &Path::from("ds=2023-01-02/a.parquet"),
+            &values,
+            &columns,
+            &PartitionLayout::Hive,
+        )
+        .expect("Expected a mismatch");
+        assert_eq!(
+            Some(&Some("2023-01-02".to_string())),
+            mismatch.path_values.get("ds")
+        );
+        assert!(partition_mismatch(
+            &Path::from("a.parquet"),
+            &values,
+            &columns,
+            &PartitionLayout::Hive
+        )
+        .is_some());
+    }
+
+    #[tokio::test]
+    async fn check_unhealthy_table() {
+        let (tempdir, _store) = crate::tests::util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-deleted-path",
+        );
+        // The fixture's own log is kept alongside its files, so its Add actions are checked
+        let root = tempdir.path().join("deltatbl-deleted-path");
+        let url = url::Url::from_directory_path(&root).expect("Failed to parse local path");
+        let table = deltalake::open_table(url.as_str())
+            .await
+            .expect("Failed to open the fixture table");
+        let files = table.get_files();
+        assert_eq!(2, files.len());
+
+        let report = check_table(&table, &ConversionOptions::default())
+            .await
+            .expect("Failed to check table");
+        assert!(report.is_healthy(), "{report:?}");
+
+        std::fs::remove_file(root.join(files[0].as_ref())).unwrap();
+        let mut contents = std::fs::read(root.join(files[1].as_ref())).unwrap();
+        contents.extend_from_slice(b"trailing garbage");
+        std::fs::write(root.join(files[1].as_ref()), contents).unwrap();
+        std::fs::write(
+            root.join("untracked.parquet"),
+            "PAR1 not really parquet PAR1",
+        )
+        .unwrap();
+
+        let report = check_table(&table, &ConversionOptions::default())
+            .await
+            .expect("Failed to check table");
+        assert!(!report.is_healthy());
+        assert_eq!(vec![files[0].to_string()], report.missing);
+        assert_eq!(1, report.size_mismatches.len());
+        assert_eq!(vec!["untracked.parquet".to_string()], report.untracked);
+        // Both the appended garbage and the fake parquet file break their footers
+        assert_eq!(2, report.incompatible.len());
+        serde_json::to_string(&report).expect("Failed to serialize the report");
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * The error module contains the errors which can be returned from oxbow
+ */
+use deltalake::operations::transaction::TransactionError;
+use deltalake::DeltaTableError;
+
+/// Convenience type for results returned from oxbow
+pub type OxbowResult<T> = Result<T, OxbowError>;
+
+/// Errors which can be returned from oxbow
+#[derive(Debug, thiserror::Error)]
+pub enum OxbowError {
+    /// The table location could not be turned into a usable URL
+    #[error("The location `{location}` is not valid: {reason}")]
+    InvalidLocation { location: String, reason: String },
+
+    /// The object store for the table location could not be constructed
+    #[error("Failed to create the object store for {location}: {source}")]
+    StoreConstruction {
+        location: String,
+        source: DeltaTableError,
+    },
+
+    /// A table schema could not be inferred from the parquet files
+    #[error("Failed to infer the table schema: {0}")]
+    SchemaInference(String),
+
+    /// Files cannot be removed from a table with `delta.appendOnly` set
+    #[error("Cannot remove {count} files from the append-only table at {location}")]
+    AppendOnlyTable { location: String, count: usize },
+
+    /// Some of the parquet files have schemas which cannot be merged with the rest
+    #[error("{} parquet files have incompatible schemas: {}", .files.len(), describe(.files))]
+    IncompatibleFiles { files: Vec<(String, String)> },
+
+    /// Some columns have types which cannot be represented in a Delta table
+    #[error("The following columns have types which cannot be represented in a Delta table: {}", describe(.columns))]
+    UnsupportedTypes { columns: Vec<(String, String)> },
+
+    /// The commit could not be made because another writer modified the table
+    #[error("Failed to commit due to a conflict with another writer: {source}")]
+    CommitConflict { source: DeltaTableError },
+
+    /// An option provided to oxbow could not be understood
+    #[error("Invalid configuration: {0}")]
+    InvalidConfiguration(String),
+
+    /// Any other error from the underlying Delta Lake library
+    #[error(transparent)]
+    Delta(DeltaTableError),
+}
+
+impl From<DeltaTableError> for OxbowError {
+    fn from(err: DeltaTableError) -> Self {
+        match err {
+            DeltaTableError::VersionAlreadyExists(_)
+            | DeltaTableError::Transaction {
+                source:
+                    TransactionError::VersionAlreadyExists(_)
+                    | TransactionError::CommitConflict(_)
+                    | TransactionError::MaxCommitAttempts(_),
+            } => Self::CommitConflict { source: err },
+            err => Self::Delta(err),
+        }
+    }
+}
+
+impl From<deltalake::ObjectStoreError> for OxbowError {
+    fn from(err: deltalake::ObjectStoreError) -> Self {
+        Self::Delta(err.into())
+    }
+}
+
+impl From<deltalake::parquet::errors::ParquetError> for OxbowError {
+    fn from(err: deltalake::parquet::errors::ParquetError) -> Self {
+        Self::Delta(err.into())
+    }
+}
+
+impl From<deltalake::arrow::error::ArrowError> for OxbowError {
+    fn from(err: deltalake::arrow::error::ArrowError) -> Self {
+        Self::Delta(err.into())
+    }
+}
+
+impl From<deltalake::protocol::ProtocolError> for OxbowError {
+    fn from(err: deltalake::protocol::ProtocolError) -> Self {
+        Self::Delta(err.into())
+    }
+}
+
+/**
+ * Format a list of names and their reasons for inclusion in an error message
+ */
+fn describe(entries: &[(String, String)]) -> String {
+    entries
+        .iter()
+        .map(|(name, reason)| format!("`{name}` ({reason})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_conflicts_are_identified() {
+        let err: OxbowError = DeltaTableError::VersionAlreadyExists(1).into();
+        assert!(matches!(err, OxbowError::CommitConflict { .. }));
+
+        let err: OxbowError = DeltaTableError::Transaction {
+            source: TransactionError::MaxCommitAttempts(15),
+        }
+        .into();
+        assert!(matches!(err, OxbowError::CommitConflict { .. }));
+
+        let err: OxbowError = DeltaTableError::Generic("oops".into()).into();
+        assert!(matches!(err, OxbowError::Delta(_)));
+    }
+
+    #[test]
+    fn unsupported_types_are_described() {
+        let err = OxbowError::UnsupportedTypes {
+            columns: vec![("time".into(), "Time32(Millisecond)".into())],
+        };
+        assert!(err.to_string().contains("`time` (Time32(Millisecond))"));
+    }
+}
@@ -0,0 +1,135 @@
+/*
+ * The filters module contains the include and exclude glob patterns which narrow down which
+ * files under a table location oxbow will consider
+ */
+use deltalake::Path;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::error::{OxbowError, OxbowResult};
+
+/**
+ * PathFilter decides whether a path, relative to the table location, should be considered by
+ * oxbow.
+ *
+ * A path is accepted when it matches any of the include patterns, or there are none, and does not
+ * match any of the exclude patterns. A single `*` does not cross directories, so whole subtrees
+ * need to be matched with a double star.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    /**
+     * Build a filter from the include and exclude glob patterns
+     */
+    pub fn new(include: &[String], exclude: &[String]) -> OxbowResult<Self> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    /// Return true if the filter would accept every path
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    /**
+     * Return true if the path should be considered by oxbow
+     */
+    pub fn matches(&self, path: &Path) -> bool {
+        let path = path.as_ref();
+        let included = match &self.include {
+            Some(include) => include.is_match(path),
+            None => true,
+        };
+        let excluded = match &self.exclude {
+            Some(exclude) => exclude.is_match(path),
+            None => false,
+        };
+        included && !excluded
+    }
+}
+
+fn glob_set(patterns: &[String]) -> OxbowResult<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter() {
+        builder.add(glob(pattern)?);
+    }
+    let set = builder.build().map_err(|err| {
+        OxbowError::InvalidConfiguration(format!("Failed to build the glob patterns: {err}"))
+    })?;
+    Ok(Some(set))
+}
+
+fn glob(pattern: &str) -> OxbowResult<Glob> {
+    // A leading slash is as close as a relative path gets to being "at the root"
+    GlobBuilder::new(pattern.trim_start_matches('/'))
+        .literal_separator(true)
+        .build()
+        .map_err(|err| {
+            OxbowError::InvalidConfiguration(format!("Invalid glob pattern `{pattern}`: {err}"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = PathFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&Path::from("year=2023/a.parquet")));
+    }
+
+    #[test]
+    fn include_subtree() {
+        let filter = PathFilter::new(&patterns(&["year=2023/**"]), &[]).unwrap();
+        assert!(filter.matches(&Path::from("year=2023/month=01/a.parquet")));
+        assert!(!filter.matches(&Path::from("year=2022/month=01/a.parquet")));
+    }
+
+    #[test]
+    fn exclude_subtrees() {
+        let filter = PathFilter::new(&[], &patterns(&["tmp/**", "**/backup/**"])).unwrap();
+        assert!(filter.matches(&Path::from("a.parquet")));
+        assert!(!filter.matches(&Path::from("tmp/a.parquet")));
+        assert!(!filter.matches(&Path::from("year=2023/backup/a.parquet")));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter = PathFilter::new(
+            &patterns(&["year=2023/**"]),
+            &patterns(&["**/*.tmp.parquet"]),
+        )
+        .unwrap();
+        assert!(filter.matches(&Path::from("year=2023/a.parquet")));
+        assert!(!filter.matches(&Path::from("year=2023/a.tmp.parquet")));
+    }
+
+    #[test]
+    fn star_does_not_cross_directories() {
+        let filter = PathFilter::new(&patterns(&["*.parquet"]), &[]).unwrap();
+        assert!(filter.matches(&Path::from("a.parquet")));
+        assert!(!filter.matches(&Path::from("year=2023/a.parquet")));
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let result = PathFilter::new(&patterns(&["year=[2023"]), &[]);
+        assert!(matches!(result, Err(OxbowError::InvalidConfiguration(_))));
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * The footers module contains the shared machinery for reading parquet footers, which every
+ * feature that needs schemas or statistics goes through so that footers are fetched concurrently
+ * and only once per conversion
+ */
+use deltalake::arrow::datatypes::Schema as ArrowSchema;
+use deltalake::parquet::arrow::parquet_to_arrow_schema;
+use deltalake::parquet::file::metadata::ParquetMetaData;
+use deltalake::storage::DeltaObjectStore;
+use deltalake::{ObjectMeta, Path};
+use futures::StreamExt;
+use tracing::log::*;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::error::OxbowResult;
+use crate::partitions::PartitionLayout;
+
+/// The number of footers read at the same time when no concurrency is configured
+pub const DEFAULT_CONCURRENCY: usize = 32;
+
+/**
+ * What later passes need from a parquet footer, which is kept in place of the whole footer
+ */
+#[derive(Debug, Clone)]
+pub struct Footer {
+    /// The Arrow schema of the file
+    pub schema: ArrowSchema,
+    /// The JSON statistics for the file's Add action, if they could be computed
+    pub stats: Option<String>,
+}
+
+/**
+ * FooterCache reads parquet footers from a store with bounded concurrency and remembers what is
+ * needed from them, so that inferring the schema and computing statistics for the same files only
+ * reads each footer once.
+ *
+ * A cache is meant to live for a single conversion, cloning it shares the cached footers. Only the
+ * [Footer] summary of each file is kept rather than its row group and column chunk metadata, so
+ * that the cache stays small for the many files validation, schema inference, planning and
+ * statistics each go over.
+ */
+#[derive(Debug, Clone)]
+pub struct FooterCache {
+    store: Arc<DeltaObjectStore>,
+    concurrency: usize,
+    layout: PartitionLayout,
+    footers: Arc<Mutex<HashMap<Path, Arc<Footer>>>>,
+}
+
+impl FooterCache {
+    /**
+     * Create an empty cache which reads at most `concurrency` footers at a time from the store,
+     * reading the partition values for statistics with the [PartitionLayout]
+     */
+    pub fn new(store: Arc<DeltaObjectStore>, concurrency: usize, layout: PartitionLayout) -> Self {
+        Self {
+            store,
+            concurrency: concurrency.max(1),
+            layout,
+            footers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The store footers are read from
+    pub fn store(&self) -> Arc<DeltaObjectStore> {
+        self.store.clone()
+    }
+
+    /// The number of footers which have been read and cached
+    pub fn len(&self) -> usize {
+        self.footers
+            .lock()
+            .expect("Footer cache lock poisoned")
+            .len()
+    }
+
+    /// Return true if no footers have been cached yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Return the [Footer] of the given file, reading it from the store if it has not been already
+     */
+    pub async fn footer(&self, file: &ObjectMeta) -> OxbowResult<Arc<Footer>> {
+        if let Some(cached) = self
+            .footers
+            .lock()
+            .expect("Footer cache lock poisoned")
+            .get(&file.location)
+        {
+            return Ok(cached.clone());
+        }
+
+        let metadata = self.read(file).await?;
+        self.summarize(file, &metadata)
+    }
+
+    /**
+     * Return the [Footer]s of all the given files, in the same order, reading up to the configured
+     * concurrency from the store at once.
+     *
+     * A failure to read one footer does not prevent the others from being read.
+     */
+    pub async fn footers(&self, files: &[ObjectMeta]) -> Vec<OxbowResult<Arc<Footer>>> {
+        futures::stream::iter(files.iter())
+            .map(|file| self.footer(file))
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+
+    /**
+     * Read the whole [ParquetMetaData] of each of the given files, with the configured
+     * concurrency, handing them to `inspect` in the same order as the files.
+     *
+     * The metadata is always read from the store since only the [Footer] is cached, which is
+     * cached along the way so that later passes do not read the footers again.
+     */
+    pub async fn inspect<T>(
+        &self,
+        files: &[ObjectMeta],
+        inspect: impl Fn(&ObjectMeta, OxbowResult<&ParquetMetaData>) -> T,
+    ) -> Vec<T> {
+        let inspect = &inspect;
+        futures::stream::iter(files.iter())
+            .map(|file| async move {
+                match self.read(file).await {
+                    Ok(metadata) => {
+                        // A schema which cannot be read is left for the later passes to report
+                        let _ = self.summarize(file, &metadata);
+                        inspect(file, Ok(&metadata))
+                    }
+                    Err(err) => inspect(file, Err(err)),
+                }
+            })
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+
+    /**
+     * Return the Arrow schema of the given file from its footer
+     */
+    pub async fn schema(&self, file: &ObjectMeta) -> OxbowResult<ArrowSchema> {
+        Ok(self.footer(file).await?.schema.clone())
+    }
+
+    async fn read(&self, file: &ObjectMeta) -> OxbowResult<Arc<ParquetMetaData>> {
+        debug!("Reading the parquet footer of {}", file.location);
+        crate::fetch_parquet_metadata(file, self.store.clone()).await
+    }
+
+    /// Cache and return the [Footer] of the file from its metadata
+    fn summarize(&self, file: &ObjectMeta, metadata: &ParquetMetaData) -> OxbowResult<Arc<Footer>> {
+        let partition_values = self.layout.partition_values_from(file.location.as_ref());
+        let footer = Arc::new(Footer {
+            schema: schema_from(metadata)?,
+            stats: crate::stats_for(&file.location, &partition_values, metadata),
+        });
+        self.footers
+            .lock()
+            .expect("Footer cache lock poisoned")
+            .insert(file.location.clone(), footer.clone());
+        Ok(footer)
+    }
+}
+
+/**
+ * Convert the schema stored in a parquet footer into an Arrow schema
+ */
+pub fn schema_from(metadata: &ParquetMetaData) -> OxbowResult<ArrowSchema> {
+    let file_metadata = metadata.file_metadata();
+    Ok(parquet_to_arrow_schema(
+        file_metadata.schema_descr(),
+        file_metadata.key_value_metadata(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn footers_are_cached() {
+        let (tempdir, store) =
+            crate::tests::util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let cache = FooterCache::new(store, 2, PartitionLayout::Hive);
+        assert!(cache.is_empty());
+
+        let footers = cache.footers(&files).await;
+        assert_eq!(files.len(), footers.len());
+        assert!(footers.iter().all(|f| f.is_ok()));
+        assert_eq!(files.len(), cache.len());
+
+        // Reading again must come from the cache, even once the files are gone
+        for file in files.iter() {
+            std::fs::remove_file(tempdir.path().join(file.location.as_ref()))
+                .expect("Failed to remove file");
+        }
+        let schema = cache.schema(&files[0]).await;
+        assert!(schema.is_ok(), "Expected the cached footer to be used");
+    }
+
+    #[tokio::test]
+    async fn inspected_footers_are_summarized() {
+        let (_tempdir, store) =
+            crate::tests::util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let cache = FooterCache::new(store, 2, PartitionLayout::Hive);
+
+        let rows = cache
+            .inspect(&files, |_, metadata| {
+                metadata.map(|m| m.file_metadata().num_rows()).ok()
+            })
+            .await;
+        assert!(rows.iter().all(|r| r.is_some()));
+        assert_eq!(files.len(), cache.len());
+
+        let footer = cache
+            .footer(&files[0])
+            .await
+            .expect("Failed to read footer");
+        assert!(!footer.schema.fields().is_empty());
+        assert!(
+            footer.stats.is_some(),
+            "Expected statistics for the Add action"
+        );
+    }
+
+    #[tokio::test]
+    async fn footers_keep_going_after_failures() {
+        let (_tempdir, store) =
+            crate::tests::util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let mut files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut missing = files[0].clone();
+        missing.location = Path::from("not/a/real.parquet");
+        files.insert(0, missing);
+
+        let footers = FooterCache::new(store, 4, PartitionLayout::Hive)
+            .footers(&files)
+            .await;
+        assert!(footers[0].is_err());
+        assert!(footers[1..].iter().all(|f| f.is_ok()));
+    }
+}
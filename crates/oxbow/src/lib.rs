@@ -2,13 +2,13 @@
  * The lib module contains the business logic of oxbow, regardless of the interface implementation
  */
 use deltalake::arrow::datatypes::Schema as ArrowSchema;
-use deltalake::parquet::arrow::async_reader::{
-    ParquetObjectReader, ParquetRecordBatchStreamBuilder,
-};
-use deltalake::partitions::DeltaTablePartition;
+use deltalake::parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use deltalake::parquet::file::metadata::ParquetMetaData;
 use deltalake::protocol::*;
 use deltalake::storage::DeltaObjectStore;
-use deltalake::{DeltaResult, DeltaTable, ObjectMeta, ObjectStore, SchemaDataType, SchemaField};
+use deltalake::{
+    DeltaTable, DeltaTableConfig, ObjectMeta, ObjectStore, Path, SchemaDataType, SchemaField,
+};
 use futures::StreamExt;
 use tracing::log::*;
 use url::Url;
@@ -16,6 +16,108 @@ use url::Url;
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+pub use error::{OxbowError, OxbowResult};
+
+pub mod checkpoint;
+pub mod doctor;
+pub mod error;
+pub mod filters;
+pub mod footers;
+pub mod lineage;
+pub mod magic;
+pub mod mapping;
+pub mod partitions;
+pub mod plan;
+pub mod properties;
+pub mod rewrite;
+pub mod schema;
+pub mod spark;
+pub mod sync;
+pub mod transactions;
+pub mod types;
+pub mod validate;
+
+/**
+ * ConversionOptions allow callers to tune how oxbow reads parquet files and creates or modifies
+ * Delta tables. The defaults are what the simpler functions like [convert] use.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOptions {
+    /// Number of files to read footers from when inferring the table schema, `None` reads them all
+    pub schema_sample: Option<usize>,
+    /// Add new nullable columns to the table schema when appended files have them
+    pub schema_evolution: bool,
+    /// Delta types to use for partition columns, which are otherwise strings
+    pub partition_types: HashMap<String, String>,
+    /// Infer the types of the partition columns without one in [Self::partition_types] from the
+    /// values in the paths of the files when creating a table
+    pub infer_partition_types: bool,
+    /// Whether files with timestamps Delta cannot represent should be rewritten before committing
+    pub timestamp_rewrite: rewrite::TimestampRewrite,
+    /// Largest number of files to add in a single commit when creating a table, `None` adds them
+    /// all in version 0
+    pub max_files_per_commit: Option<usize>,
+    /// Write a checkpoint once a table has been created, or of an existing table when converting
+    /// it again
+    pub checkpoint_after_conversion: bool,
+    /// Number of commits between checkpoints for tables without a `delta.checkpointInterval`,
+    /// `None` uses [checkpoint::DEFAULT_INTERVAL]
+    pub checkpoint_interval: Option<u64>,
+    /// Remove log files which have expired under `delta.logRetentionDuration` after writing a
+    /// checkpoint
+    pub expired_log_cleanup: bool,
+    /// Number of parquet footers to read at the same time, `None` uses
+    /// [footers::DEFAULT_CONCURRENCY]
+    pub footer_concurrency: Option<usize>,
+    /// Glob patterns, relative to the table location, of the only files to consider
+    pub include: Vec<String>,
+    /// Glob patterns, relative to the table location, of files to ignore
+    pub exclude: Vec<String>,
+    /// Extensions of the files to treat as parquet, empty uses [magic::DEFAULT_EXTENSIONS]
+    pub parquet_extensions: Vec<String>,
+    /// Check the magic bytes of files without one of the parquet extensions, to find parquet
+    /// files with other or no extensions
+    pub sniff_content: bool,
+    /// Whether files should be validated before committing, and what to do with invalid ones
+    pub quarantine: validate::Quarantine,
+    /// How partition values are read from the paths of files
+    pub partition_layout: partitions::PartitionLayout,
+    /// Table properties, such as `delta.appendOnly`, to create tables with
+    pub table_properties: HashMap<String, String>,
+    /// The name to create tables with
+    pub table_name: Option<String>,
+    /// The description to create tables with
+    pub table_description: Option<String>,
+    /// Additional keys to record in the commitInfo of every commit, see [lineage::commit_metadata]
+    pub commit_metadata: serde_json::Map<String, serde_json::Value>,
+    /// The transaction identifying the batches of events which added or removed files. When the
+    /// table has already applied it the files are skipped, otherwise it is committed along with
+    /// the files. Callers combining several batches should use the latest of their sequences and
+    /// leave out the files of the batches which have been applied
+    pub transaction: Option<transactions::Transaction>,
+}
+
+impl ConversionOptions {
+    /**
+     * Create a fresh [footers::FooterCache] for a single conversion against the store
+     */
+    pub fn footer_cache(&self, store: Arc<DeltaObjectStore>) -> footers::FooterCache {
+        footers::FooterCache::new(
+            store,
+            self.footer_concurrency
+                .unwrap_or(footers::DEFAULT_CONCURRENCY),
+            self.partition_layout.clone(),
+        )
+    }
+
+    /**
+     * Build the [filters::PathFilter] from the include and exclude patterns
+     */
+    pub fn path_filter(&self) -> OxbowResult<filters::PathFilter> {
+        filters::PathFilter::new(&self.include, &self.exclude)
+    }
+}
+
 /**
  * convert is the main function to be called by the CLI or other "one shot" executors which just
  * need to take a given location and convert it all at once
@@ -23,65 +125,196 @@ use std::sync::Arc;
 pub async fn convert(
     location: &str,
     storage_options: Option<HashMap<String, String>>,
-) -> DeltaResult<DeltaTable> {
-    let table_result = match storage_options {
-        Some(ref so) => deltalake::open_table_with_storage_options(&location, so.clone()).await,
-        None => deltalake::open_table(&location).await,
-    };
+) -> OxbowResult<DeltaTable> {
+    convert_with_options(location, storage_options, &ConversionOptions::default()).await
+}
+
+/**
+ * Convert the given location with the provided [ConversionOptions]
+ */
+pub async fn convert_with_options(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+    options: &ConversionOptions,
+) -> OxbowResult<DeltaTable> {
+    let table_result = open_table(location, storage_options.clone()).await;
 
     match table_result {
         Err(e) => {
             info!("No Delta table at {}: {:?}", location, e);
-            /*
-             * Parse the given location as a URL in a way that can be passed into
-             * some delta APIs
-             */
-            let location = match Url::parse(location) {
-                Ok(parsed) => parsed,
-                Err(_) => {
-                    let absolute = std::fs::canonicalize(location)
-                        .expect("Failed to canonicalize table location");
-                    Url::from_file_path(absolute)
-                        .expect("Failed to parse the location as a file path")
-                }
-            };
-            let store = object_store_for(&location, storage_options);
-            let files = discover_parquet_files(store.clone()).await?;
+            let location = location_url(location)?;
+            let store = object_store_for(&location, storage_options)?;
+            let files = discover_parquet_files_with_options(store.clone(), options).await?;
             debug!(
                 "Files identified for turning into a delta table: {:?}",
                 files
             );
-            create_table_with(&files, store.clone()).await
+            create_table_with_options(&files, store.clone(), options).await
         }
-        Ok(table) => {
+        Ok(mut table) => {
             warn!("There is already a Delta table at: {}", table);
+            if options.checkpoint_after_conversion {
+                let version = table.version();
+                checkpoint::checkpoint_version(&mut table, version, options).await?;
+            }
             Ok(table)
         }
     }
 }
 
+/**
+ * Plan the conversion of the given location without modifying it, returning a [plan::ConversionPlan]
+ * which describes the table that [convert_with_options] would create
+ */
+pub async fn plan(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+    options: &ConversionOptions,
+) -> OxbowResult<plan::ConversionPlan> {
+    let table_result = open_table(location, storage_options.clone()).await;
+
+    match table_result {
+        Err(e) => {
+            info!("No Delta table at {}: {:?}", location, e);
+            let location = location_url(location)?;
+            let store = object_store_for(&location, storage_options)?;
+            let files = discover_parquet_files_with_options(store.clone(), options).await?;
+            if files.is_empty() {
+                warn!("There are no parquet files to convert at {location}");
+                return Ok(plan::ConversionPlan::default());
+            }
+            plan::plan_for(&files, &options.footer_cache(store), options).await
+        }
+        Ok(table) => {
+            warn!("There is already a Delta table at: {}", table);
+            Ok(plan::ConversionPlan {
+                existing_version: Some(table.version()),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/**
+ * Sync the existing Delta table at the location with the files in storage, see [sync::sync_table]
+ */
+pub async fn sync(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+    options: &ConversionOptions,
+) -> OxbowResult<sync::SyncSummary> {
+    let mut table = open_table(location, storage_options).await?;
+    sync::sync_table(&mut table, options).await
+}
+
+/**
+ * Check the health of the existing Delta table at the location without modifying it, see
+ * [doctor::check_table]
+ */
+pub async fn doctor(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+    options: &ConversionOptions,
+) -> OxbowResult<doctor::HealthReport> {
+    let table = open_table(location, storage_options).await?;
+    doctor::check_table(&table, options).await
+}
+
+/**
+ * Write a checkpoint of the latest version of the existing Delta table at the location and remove
+ * its expired log files, see [checkpoint::cleanup_expired_logs]
+ */
+pub async fn cleanup_logs(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+    options: &ConversionOptions,
+) -> OxbowResult<checkpoint::CheckpointSummary> {
+    let mut table = open_table(location, storage_options).await?;
+    let options = ConversionOptions {
+        expired_log_cleanup: true,
+        ..options.clone()
+    };
+    let version = table.version();
+    checkpoint::checkpoint_version(&mut table, version, &options).await
+}
+
+/**
+ * Open the existing Delta table at the location, with the storage options when there are any
+ */
+async fn open_table(
+    location: &str,
+    storage_options: Option<HashMap<String, String>>,
+) -> Result<DeltaTable, deltalake::DeltaTableError> {
+    match storage_options {
+        Some(so) => deltalake::open_table_with_storage_options(location, so).await,
+        None => deltalake::open_table(location).await,
+    }
+}
+
+/**
+ * Parse the given location as a URL in a way that can be passed into some delta APIs
+ */
+fn location_url(location: &str) -> OxbowResult<Url> {
+    match Url::parse(location) {
+        Ok(parsed) => Ok(parsed),
+        Err(_) => {
+            let absolute =
+                std::fs::canonicalize(location).map_err(|err| OxbowError::InvalidLocation {
+                    location: location.into(),
+                    reason: err.to_string(),
+                })?;
+            Url::from_file_path(&absolute).map_err(|_| OxbowError::InvalidLocation {
+                location: location.into(),
+                reason: format!("{} cannot be used as a file URL", absolute.display()),
+            })
+        }
+    }
+}
+
 /**
  * Create the ObjectStore for the given location
  */
 pub fn object_store_for(
     location: &Url,
     storage_options: Option<HashMap<String, String>>,
-) -> Arc<DeltaObjectStore> {
-    let options = match storage_options {
-        None => HashMap::default(),
-        Some(s) => s,
-    };
-    Arc::new(DeltaObjectStore::try_new(location.clone(), options).expect("Failed to make store"))
+) -> OxbowResult<Arc<DeltaObjectStore>> {
+    let options = storage_options.unwrap_or_default();
+    let store = DeltaObjectStore::try_new(location.clone(), options).map_err(|source| {
+        OxbowError::StoreConstruction {
+            location: location.to_string(),
+            source,
+        }
+    })?;
+    Ok(Arc::new(store))
 }
 
 /**
  * Discover `.parquet` files which are present in the location
  */
-pub async fn discover_parquet_files(
+pub async fn discover_parquet_files(store: Arc<DeltaObjectStore>) -> OxbowResult<Vec<ObjectMeta>> {
+    discover_parquet_files_with_options(store, &ConversionOptions::default()).await
+}
+
+/**
+ * Discover parquet files which are present in the location and match the include, exclude and
+ * extension settings of the [ConversionOptions]
+ */
+pub async fn discover_parquet_files_with_options(
+    store: Arc<DeltaObjectStore>,
+    options: &ConversionOptions,
+) -> OxbowResult<Vec<ObjectMeta>> {
+    discover(store, &options.path_filter()?, options).await
+}
+
+async fn discover(
     store: Arc<DeltaObjectStore>,
-) -> deltalake::DeltaResult<Vec<ObjectMeta>> {
+    filter: &filters::PathFilter,
+    options: &ConversionOptions,
+) -> OxbowResult<Vec<ObjectMeta>> {
     info!("Discovering parquet files for {store:?}");
     let mut result = vec![];
+    let mut unknown = vec![];
+    let mut markers = vec![];
     let mut iter = store.list(None).await?;
 
     /*
@@ -90,115 +323,324 @@ pub async fn discover_parquet_files(
     while let Some(path) = iter.next().await {
         // Result<ObjectMeta> has been yielded
         if let Ok(meta) = path {
-            if let Some(ext) = meta.location.extension() {
-                match ext {
-                    "parquet" => {
-                        if let Some(filename) = meta.location.filename() {
-                            if !filename.ends_with(".checkpoint.parquet") {
-                                debug!("Discovered file: {:?}", meta);
-                                result.push(meta);
-                            }
-                        }
-                    }
-                    &_ => {}
+            if spark::is_committed_marker(&meta.location) {
+                markers.push(meta);
+                continue;
+            }
+            if let Some(filename) = meta.location.filename() {
+                if filename.ends_with(".checkpoint.parquet") {
+                    continue;
                 }
             }
+            if spark::is_hidden(&meta.location) {
+                debug!("Ignoring hidden file: {:?}", meta.location);
+                continue;
+            }
+            if !filter.matches(&meta.location) {
+                debug!("Ignoring filtered file: {:?}", meta.location);
+                continue;
+            }
+            if magic::has_parquet_extension(&meta.location, &options.parquet_extensions) {
+                debug!("Discovered file: {:?}", meta);
+                result.push(meta);
+            } else if options.sniff_content {
+                unknown.push(meta);
+            }
         }
     }
-    Ok(result)
+    drop(iter);
+
+    if !unknown.is_empty() {
+        info!("Checking the magic bytes of {} other files", unknown.len());
+        let concurrency = options
+            .footer_concurrency
+            .unwrap_or(footers::DEFAULT_CONCURRENCY);
+        result.append(&mut magic::sniff_parquet_files(unknown, store.clone(), concurrency).await);
+    }
+    // Originals left in place by a timestamp rewrite are represented by their rewritten copies
+    let files = rewrite::without_rewritten_originals(
+        spark::committed_files(result, &markers, store).await?,
+    );
+    Ok(matching_layout(files, &options.partition_layout))
+}
+
+/**
+ * Return the files whose partition values can be read with the layout, reporting the rest which
+ * would otherwise be committed without any values for the partition columns
+ */
+fn matching_layout(
+    files: Vec<ObjectMeta>,
+    layout: &partitions::PartitionLayout,
+) -> Vec<ObjectMeta> {
+    files
+        .into_iter()
+        .filter(|file| {
+            let matches = layout.matches(file.location.as_ref());
+            if !matches {
+                warn!(
+                    "Skipping {} which does not match the partition template",
+                    file.location
+                );
+            }
+            matches
+        })
+        .collect()
+}
+
+/**
+ * Return the files whose partition values can be read as the types of the table's partition
+ * columns, reporting the rest which would otherwise break the readers of the table
+ */
+fn matching_partition_types(
+    files: Vec<ObjectMeta>,
+    table: &DeltaTable,
+    layout: &partitions::PartitionLayout,
+) -> OxbowResult<Vec<ObjectMeta>> {
+    let schema = table.get_schema()?;
+    let types: HashMap<&str, &str> = table
+        .get_metadata()?
+        .partition_columns
+        .iter()
+        .filter_map(
+            |column| match schema.get_field_with_name(column).ok()?.get_type() {
+                SchemaDataType::primitive(type_name) => Some((column.as_str(), type_name.as_str())),
+                _ => None,
+            },
+        )
+        .collect();
+
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            let values = layout.partition_values_from(file.location.as_ref());
+            let mismatch = values.iter().find_map(|(column, value)| {
+                let type_name = types.get(column.as_str())?;
+                let value = value.as_ref()?;
+                (!partitions::is_value_of(value, type_name)).then_some((column, value, type_name))
+            });
+            if let Some((column, value, type_name)) = mismatch {
+                warn!(
+                    "Skipping {} whose value `{value}` of the partition column `{column}` is not a {type_name}",
+                    file.location
+                );
+            }
+            mismatch.is_none()
+        })
+        .collect())
 }
 
 /**
  * Create a Delta table with the given series of files at the specified location
  */
 pub async fn create_table_with(
-    files: &Vec<ObjectMeta>,
+    files: &[ObjectMeta],
+    store: Arc<DeltaObjectStore>,
+) -> OxbowResult<DeltaTable> {
+    create_table_with_options(files, store, &ConversionOptions::default()).await
+}
+
+/**
+ * Create a Delta table with the given series of files and [ConversionOptions]
+ *
+ * The table schema is unified from the footers of the files, and creation will fail if any of
+ * the files has a schema which cannot be merged with the rest, unless quarantining is enabled in
+ * which case those files are left out but never quarantined. If a table already exists at the
+ * location it is returned unchanged, use [append_to_table_with_options] to add files to it.
+ */
+pub async fn create_table_with_options(
+    files: &[ObjectMeta],
     store: Arc<DeltaObjectStore>,
-) -> DeltaResult<DeltaTable> {
+    options: &ConversionOptions,
+) -> OxbowResult<DeltaTable> {
     use deltalake::operations::create::CreateBuilder;
-    use deltalake::schema::Schema;
 
-    let partitions = partition_columns_from(files);
-    let smallest = find_smallest_file(files);
-    if smallest.is_none() {
-        let msg = "Failed to find the smallest parquet file, which is fatal";
+    let mut configuration = properties::table_configuration(&options.table_properties)?;
+    if store.is_delta_table_location().await? {
+        // Like SaveMode::Ignore, which must not go on to commit the remaining chunks
+        warn!("There is already a Delta table at: {}", store.root_uri());
+        let mut table = DeltaTable::new(store, DeltaTableConfig::default());
+        table.load().await?;
+        return Ok(table);
+    }
+    let files = matching_layout(files.to_vec(), &options.partition_layout);
+    if files.is_empty() {
+        let msg = "Cannot create a table without any parquet files, which is fatal";
         error!("{}", &msg);
-        return Err(deltalake::DeltaTableError::Generic(msg.into()));
+        return Err(OxbowError::SchemaInference(msg.into()));
     }
-    let smallest = smallest.unwrap();
-    info!(
-        "Using the smallest parquet file for schema inference: {:?}",
-        smallest.location
-    );
-    let file_reader = ParquetObjectReader::new(store.clone(), smallest.clone());
-    let arrow_schema = ParquetRecordBatchStreamBuilder::new(file_reader)
-        .await?
-        .build()?
-        .schema()
-        .clone();
-    debug!("Read schema from Parquet file: {:?}", arrow_schema);
-
-    let mut conversions: Vec<Arc<deltalake::arrow::datatypes::Field>> = vec![];
-
-    for field in arrow_schema.fields().iter() {
-        match field.data_type() {
-            deltalake::arrow::datatypes::DataType::Timestamp(unit, tz) => match unit {
-                deltalake::arrow::datatypes::TimeUnit::Millisecond => {
-                    warn!("I have been asked to create a table with a Timestamp(millis) column ({}) that I cannot handle. Cowardly setting the Delta schema to pretend it is a Timestamp(micros)", field.name());
-                    let field = deltalake::arrow::datatypes::Field::new(
-                        field.name(),
-                        deltalake::arrow::datatypes::DataType::Timestamp(
-                            deltalake::arrow::datatypes::TimeUnit::Microsecond,
-                            tz.clone(),
-                        ),
-                        field.is_nullable(),
-                    );
-                    conversions.push(Arc::new(field));
-                }
-                _ => conversions.push(field.clone()),
-            },
-            _ => conversions.push(field.clone()),
+    let footers = options.footer_cache(store.clone());
+    let validated;
+    let files = match options.quarantine {
+        validate::Quarantine::Disabled => files.as_slice(),
+        mode => {
+            validated = validate::validate_files(&files, &footers, None).await;
+            validate::quarantine(&validated.invalid, mode, store.clone()).await;
+            validated.valid.as_slice()
         }
+    };
+    if files.is_empty() {
+        let msg = "Cannot create a table since none of the parquet files are valid";
+        error!("{}", &msg);
+        return Err(OxbowError::SchemaInference(msg.into()));
     }
-
-    let arrow_schema = ArrowSchema::new_with_metadata(conversions, arrow_schema.metadata.clone());
-
-    let schema: deltalake::schema::Schema = Schema::try_from(&arrow_schema)
-        .expect("Failed to convert the schema for creating the table");
-
-    let mut columns = schema.get_fields().clone();
-
-    for partition in &partitions {
-        // Only add the partition if it does not already exist in the schema
-        if schema.get_field_with_name(partition).is_err() {
-            let field = SchemaField::new(
-                partition.into(),
-                SchemaDataType::primitive("string".into()),
-                true,
-                HashMap::new(),
-            );
-            columns.push(field);
+    let rewritten = match options.timestamp_rewrite {
+        rewrite::TimestampRewrite::Disabled => None,
+        _ => Some(rewrite::rewrite_timestamps(files, &footers).await?),
+    };
+    let files = match &rewritten {
+        Some(rewritten) => rewritten.files.as_slice(),
+        None => files,
+    };
+    let plan = plan::plan_for(files, &footers, options).await?;
+    if !plan.skipped.is_empty() {
+        let err = OxbowError::IncompatibleFiles {
+            files: plan
+                .skipped
+                .iter()
+                .map(|skipped| (skipped.path.clone(), skipped.reason.clone()))
+                .collect(),
+        };
+        // Files which only conflict with the schema are left where they are, never quarantined
+        if options.quarantine == validate::Quarantine::Disabled {
+            error!("Refusing to create a table: {err}");
+            return Err(err);
         }
+        warn!("Creating a table without some of the files: {err}");
     }
+    let planned: HashSet<&str> = plan.files.iter().map(|f| f.path.as_str()).collect();
+    let files: Vec<ObjectMeta> = files
+        .iter()
+        .filter(|f| planned.contains(f.location.as_ref()))
+        .cloned()
+        .collect();
+    let columns = plan
+        .schema
+        .map(|schema| schema.get_fields().clone())
+        .unwrap_or_default();
 
     /*
      * Create and persist the table
      */
-    let actions = add_actions_for(files);
-    CreateBuilder::new()
+    let mut actions = add_actions_for(&files, &footers, options).await;
+    let chunk_size = options
+        .max_files_per_commit
+        .filter(|max| *max > 0)
+        .unwrap_or(actions.len());
+    let remaining = actions.split_off(chunk_size.min(actions.len()));
+
+    let protocol = match plan.column_mapping {
+        true => {
+            configuration.extend(mapping::configuration(&columns));
+            mapping::PROTOCOL
+        }
+        false => Protocol {
+            min_reader_version: deltalake::operations::MAX_SUPPORTED_READER_VERSION,
+            min_writer_version: deltalake::operations::MAX_SUPPORTED_WRITER_VERSION,
+        },
+    };
+    // The same operation the CreateBuilder records, which oxbow has to write the commitInfo for
+    let operation = DeltaOperation::Create {
+        mode: SaveMode::Ignore,
+        location: store.root_uri(),
+        protocol: protocol.clone(),
+        metadata: deltalake::table::DeltaTableMetaData::new(
+            options.table_name.clone(),
+            options.table_description.clone(),
+            None,
+            deltalake::schema::Schema::new(columns.clone()),
+            plan.partition_columns.clone(),
+            configuration.clone(),
+        ),
+    };
+    let file_count = actions.len();
+    actions.push(lineage::commit_info(&operation, None, options, file_count));
+
+    let mut builder = CreateBuilder::new()
         .with_object_store(store.clone())
+        .with_partition_columns(plan.partition_columns.clone())
+        .with_save_mode(SaveMode::Ignore);
+    if plan.column_mapping {
+        builder = builder.with_actions(vec![Action::protocol(protocol)]);
+    }
+    if let Some(name) = &options.table_name {
+        builder = builder.with_table_name(name);
+    }
+    if let Some(description) = &options.table_description {
+        builder = builder.with_comment(description);
+    }
+    let mut table = builder
+        .with_configuration(configuration)
         .with_columns(columns)
-        .with_partition_columns(partitions)
         .with_actions(actions)
-        .with_save_mode(SaveMode::Ignore)
-        .await
+        .await?;
+
+    for chunk in remaining.chunks(chunk_size) {
+        let operation = DeltaOperation::Write {
+            mode: SaveMode::Append,
+            partition_by: Some(plan.partition_columns.clone()),
+            predicate: None,
+        };
+        let mut actions = chunk.to_vec();
+        actions.push(lineage::commit_info(
+            &operation,
+            Some(table.version()),
+            options,
+            chunk.len(),
+        ));
+        let version = deltalake::operations::transaction::commit(
+            table.object_store().as_ref(),
+            &actions,
+            operation,
+            table.get_state(),
+            None,
+        )
+        .await?;
+        debug!("Committed {} files in version {version}", chunk.len());
+        table.update().await?;
+        checkpoint::checkpoint_after_commit(&mut table, version, options).await;
+    }
+
+    if options.checkpoint_after_conversion {
+        let version = table.version();
+        checkpoint::checkpoint_version(&mut table, version, options).await?;
+    }
+
+    if let (Some(rewritten), rewrite::TimestampRewrite::RemoveOriginals) =
+        (&rewritten, options.timestamp_rewrite)
+    {
+        rewrite::remove_originals(&rewritten.originals, store).await;
+    }
+    Ok(table)
 }
 
 /**
  * Append the given files to an already existing and initialized Delta Table
  */
-pub async fn append_to_table(files: &[ObjectMeta], table: &mut DeltaTable) -> DeltaResult<i64> {
-    let existing_files = table.get_files();
+pub async fn append_to_table(files: &[ObjectMeta], table: &mut DeltaTable) -> OxbowResult<i64> {
+    append_to_table_with_options(files, table, &ConversionOptions::default()).await
+}
+
+/**
+ * Append the given files to an already existing and initialized Delta Table with the provided
+ * [ConversionOptions]
+ *
+ * When [ConversionOptions::schema_evolution] is enabled, any columns in the appended files which
+ * the table does not yet have will be added to the table schema as nullable columns in the same
+ * commit as the files themselves.
+ */
+pub async fn append_to_table_with_options(
+    files: &[ObjectMeta],
+    table: &mut DeltaTable,
+    options: &ConversionOptions,
+) -> OxbowResult<i64> {
+    table.update().await?;
+    if is_applied(table, options) {
+        debug!("Skipping the files added by batches which were already applied");
+        return Ok(table.version());
+    }
+    let files = matching_layout(files.to_vec(), &options.partition_layout);
+    let files = &matching_partition_types(files, table, &options.partition_layout)?;
+    let existing_files = table.get_file_set();
     let new_files: Vec<ObjectMeta> = files
         .iter()
         .filter(|f| !existing_files.contains(&f.location))
@@ -210,133 +652,410 @@ pub async fn append_to_table(files: &[ObjectMeta], table: &mut DeltaTable) -> De
         return Ok(table.version());
     }
 
-    let actions = add_actions_for(&new_files);
+    let footers = options.footer_cache(table.object_store());
+    let new_files = match options.quarantine {
+        validate::Quarantine::Disabled => new_files,
+        mode => {
+            let validated =
+                validate::validate_files(&new_files, &footers, Some(table.get_schema()?)).await;
+            validate::quarantine(&validated.invalid, mode, table.object_store()).await;
+            validated.valid
+        }
+    };
+
+    if new_files.is_empty() {
+        debug!("No valid files to add on {table:?}, skipping a commit");
+        return Ok(table.version());
+    }
+
+    let rewritten = match options.timestamp_rewrite {
+        rewrite::TimestampRewrite::Disabled => None,
+        _ => Some(rewrite::rewrite_timestamps(&new_files, &footers).await?),
+    };
+    let new_files: Vec<ObjectMeta> = match &rewritten {
+        // A redelivered file may have already been rewritten and committed
+        Some(rewritten) => rewritten
+            .files
+            .iter()
+            .filter(|f| !existing_files.contains(&f.location))
+            .cloned()
+            .collect(),
+        None => new_files,
+    };
+
+    if new_files.is_empty() {
+        debug!("No new files to add on {table:?} after rewriting, skipping a commit");
+        return Ok(table.version());
+    }
+
+    let mut actions = vec![];
+
+    if options.schema_evolution {
+        if let Some(metadata) = evolved_metadata_for(&new_files, table, &footers).await? {
+            actions.push(Action::metaData(metadata));
+        }
+    }
+
+    actions.append(&mut add_actions_for(&new_files, &footers, options).await);
+    actions.extend(options.transaction.iter().map(|txn| txn.action()));
 
-    deltalake::operations::transaction::commit(
+    let operation = DeltaOperation::Write {
+        mode: SaveMode::Append,
+        partition_by: Some(options.partition_layout.columns_from(files)),
+        predicate: None,
+    };
+    actions.push(lineage::commit_info(
+        &operation,
+        Some(table.version()),
+        options,
+        new_files.len(),
+    ));
+    let version = deltalake::operations::transaction::commit(
         table.object_store().as_ref(),
         &actions,
-        DeltaOperation::Write {
-            mode: SaveMode::Append,
-            partition_by: Some(partition_columns_from(files)),
-            predicate: None,
-        },
+        operation,
         table.get_state(),
         None,
     )
-    .await
+    .await?;
+    checkpoint::checkpoint_after_commit(table, version, options).await;
+
+    if let (Some(rewritten), rewrite::TimestampRewrite::RemoveOriginals) =
+        (&rewritten, options.timestamp_rewrite)
+    {
+        rewrite::remove_originals(&rewritten.originals, table.object_store()).await;
+    }
+    Ok(version)
+}
+
+/**
+ * Return true if the table has already applied the [ConversionOptions::transaction]
+ */
+fn is_applied(table: &DeltaTable, options: &ConversionOptions) -> bool {
+    options
+        .transaction
+        .as_ref()
+        .map(|txn| txn.is_applied(table))
+        .unwrap_or(false)
+}
+
+/**
+ * Compare the schemas of the given files to the table's schema and return the [MetaData] which
+ * adds any new columns to the table, or `None` if the table already has every column
+ *
+ * Only new top-level columns are added, and always as nullable since the files already in the
+ * table will not have values for them.
+ */
+async fn evolved_metadata_for(
+    files: &[ObjectMeta],
+    table: &DeltaTable,
+    footers: &footers::FooterCache,
+) -> OxbowResult<Option<MetaData>> {
+    let table_metadata = table.get_metadata()?;
+    let table_schema = &table_metadata.schema;
+    let unified = schema::unify_schemas(files, footers, None).await?;
+    if !unified.incompatible.is_empty() {
+        warn!(
+            "Some appended files could not be considered for schema evolution: {:?}",
+            unified.incompatible
+        );
+    }
+
+    let mut fields = table_schema.get_fields().clone();
+    let mut added = vec![];
+
+    let column_mapping = mapping::mode_of(table);
+    let (schema, _) = delta_schema_from(&unified.schema)?;
+    for field in schema.get_fields() {
+        if table_schema.get_field_with_name(field.get_name()).is_err()
+            && !table_metadata
+                .partition_columns
+                .iter()
+                .any(|p| p == field.get_name())
+        {
+            if column_mapping.is_none()
+                && mapping::requires_column_mapping(std::slice::from_ref(field))
+            {
+                warn!(
+                    "The new column `{}` cannot be added to a table without column mapping",
+                    field.get_name()
+                );
+                continue;
+            }
+            added.push(field.get_name().to_string());
+            fields.push(SchemaField::new(
+                field.get_name().to_string(),
+                field.get_type().clone(),
+                true,
+                field.get_metadata().clone(),
+            ));
+        }
+    }
+
+    if added.is_empty() {
+        return Ok(None);
+    }
+    info!("Evolving the table schema with the new columns: {added:?}");
+
+    let mut metadata = table_metadata.clone();
+    if column_mapping.is_some() {
+        // New ids must not reuse those of columns which have since been dropped
+        let max_id = table_metadata
+            .configuration
+            .get(mapping::MAX_COLUMN_ID_KEY)
+            .cloned()
+            .flatten()
+            .and_then(|id| id.parse().ok())
+            .unwrap_or_else(|| mapping::max_column_id(&fields));
+        let (mapped, max_id) = mapping::with_column_mapping(fields, max_id);
+        fields = mapped;
+        metadata.configuration.insert(
+            mapping::MAX_COLUMN_ID_KEY.to_string(),
+            Some(max_id.to_string()),
+        );
+    }
+    metadata.schema = deltalake::schema::Schema::new(fields);
+    Ok(Some(MetaData::try_from(metadata)?))
 }
 
 /// Remove the given files from an already existing and initialized [DeltaTable]
-pub async fn remove_from_table(files: &[ObjectMeta], table: &mut DeltaTable) -> DeltaResult<i64> {
-    let actions = remove_actions_for(files);
+pub async fn remove_from_table(files: &[ObjectMeta], table: &mut DeltaTable) -> OxbowResult<i64> {
+    remove_from_table_with_options(files, table, &ConversionOptions::default()).await
+}
 
-    if actions.is_empty() {
+/// Remove the given files from an already existing and initialized [DeltaTable] with the provided
+/// [ConversionOptions]
+///
+/// The table is updated to its latest version first. Files which it does not track, such as
+/// originals deleted after a [rewrite::TimestampRewrite::RemoveOriginals], are skipped rather than
+/// committed as removals. Tables with `delta.appendOnly` set refuse any other removal with
+/// [OxbowError::AppendOnlyTable].
+pub async fn remove_from_table_with_options(
+    files: &[ObjectMeta],
+    table: &mut DeltaTable,
+    options: &ConversionOptions,
+) -> OxbowResult<i64> {
+    table.update().await?;
+    if is_applied(table, options) {
+        debug!("Skipping the files removed by batches which were already applied");
         return Ok(table.version());
     }
+    let tracked = table.get_file_set();
+    let files: Vec<ObjectMeta> = files
+        .iter()
+        .filter(|file| {
+            let is_tracked = tracked.contains(&file.location);
+            if !is_tracked {
+                debug!(
+                    "Skipping the removal of {} which the table does not track",
+                    file.location
+                );
+            }
+            is_tracked
+        })
+        .cloned()
+        .collect();
+    let mut actions = remove_actions_for(&files, options);
 
-    deltalake::operations::transaction::commit(
+    if actions.is_empty() {
+        return Ok(table.version());
+    }
+    let file_count = actions.len();
+    if properties::is_append_only(table) {
+        let err = OxbowError::AppendOnlyTable {
+            location: table.table_uri(),
+            count: file_count,
+        };
+        error!("Refusing to remove files: {err}");
+        return Err(err);
+    }
+    actions.extend(options.transaction.iter().map(|txn| txn.action()));
+
+    let operation = DeltaOperation::Delete { predicate: None };
+    actions.push(lineage::commit_info(
+        &operation,
+        Some(table.version()),
+        options,
+        file_count,
+    ));
+    let version = deltalake::operations::transaction::commit(
         table.object_store().as_ref(),
         &actions,
-        DeltaOperation::Delete { predicate: None },
+        operation,
         table.get_state(),
         None,
     )
-    .await
+    .await?;
+    checkpoint::checkpoint_after_commit(table, version, options).await;
+    Ok(version)
 }
 
 /**
- * Take an iterator of files and determine what looks like a partition column from it
+ * Convert the Arrow schema read from parquet files into a Delta schema
+ *
+ * See [types::delta_compatible_schema] for how types without a direct Delta equivalent are handled
  */
-fn partition_columns_from(files: &[ObjectMeta]) -> Vec<String> {
-    // The HashSet is only to prevent collecting redundant partitions
-    let mut results = HashSet::new();
+fn delta_schema_from(
+    arrow_schema: &ArrowSchema,
+) -> OxbowResult<(deltalake::schema::Schema, Vec<types::TypeCoercion>)> {
+    use deltalake::arrow::datatypes::DataType;
+    use deltalake::schema::Schema;
 
-    /*
-     * The nested iteration here is required to evaluate nested partitions
-     */
-    for file in files.iter() {
-        let _ = file
-            .location
-            .as_ref()
-            .split('/')
-            .flat_map(DeltaTablePartition::try_from)
-            .map(|p| results.insert(p.key.to_string()))
-            .collect::<Vec<_>>();
-    }
+    let (arrow_schema, coercions) = types::delta_compatible_schema(arrow_schema)?;
 
-    results.into_iter().collect()
-}
+    for coercion in coercions.iter() {
+        if let DataType::Timestamp(unit, _) = &coercion.from {
+            warn!("The column `{}` is a Timestamp({unit:?}) which Delta cannot represent. Cowardly setting the Delta schema to pretend it is a Timestamp(micros)", coercion.column);
+        }
+    }
 
-/**
- * Return all the partitions from the given path buf
- */
-fn partitions_from(path_str: &str) -> Vec<DeltaTablePartition> {
-    path_str
-        .split('/')
-        .flat_map(DeltaTablePartition::try_from)
-        .collect()
+    Ok((Schema::try_from(&arrow_schema)?, coercions))
 }
 
 /**
  * Provide a series of Add actions for the given ObjectMeta entries
  *
  * This is a critical translation layer between discovered parquet files and how those would be
- * represented inside of the log
+ * represented inside of the log. Column statistics are read from each file's footer through the
+ * [footers::FooterCache], and partition values with the [ConversionOptions::partition_layout].
+ *
+ * Files whose footers cannot be read or summarized will still have an Add action, just without
+ * any statistics, since that is no worse than what readers would otherwise get.
  */
-pub fn add_actions_for(files: &[ObjectMeta]) -> Vec<Action> {
-    files
-        .iter()
-        .map(|om| Add {
-            path: om.location.to_string(),
-            size: om.size as i64,
-            modification_time: om.last_modified.timestamp_millis(),
-            data_change: true,
-            deletion_vector: None,
-            stats: None,
-            stats_parsed: None,
-            tags: None,
-            partition_values: partitions_from(om.location.as_ref())
-                .into_iter()
-                .map(|p| ((p.key), Some(p.value)))
-                .collect(),
-            partition_values_parsed: None,
-        })
-        .map(Action::add)
-        .collect()
-}
-
-/// Provide a series of Remove actions for the given [ObjectMeta] entries
-pub fn remove_actions_for(files: &[ObjectMeta]) -> Vec<Action> {
+pub async fn add_actions_for(
+    files: &[ObjectMeta],
+    footers: &footers::FooterCache,
+    options: &ConversionOptions,
+) -> Vec<Action> {
     files
         .iter()
-        .map(|om| Remove {
-            path: om.location.to_string(),
-            data_change: true,
-            size: Some(om.size as i64),
-            partition_values: Some(
-                partitions_from(om.location.as_ref())
-                    .into_iter()
-                    .map(|p| ((p.key), Some(p.value)))
-                    .collect(),
-            ),
-            ..Default::default()
+        .zip(footers.footers(files).await)
+        .map(|(om, footer)| {
+            let stats = match footer {
+                Ok(footer) => footer.stats.clone(),
+                Err(err) => {
+                    warn!(
+                        "Failed to read the Parquet footer of {}, no statistics will be recorded: {err:?}",
+                        om.location
+                    );
+                    None
+                }
+            };
+            Action::add(add_for(om, stats, &options.partition_layout))
         })
-        .map(Action::remove)
         .collect()
 }
 
 /**
- * Return the smallest file from the given set of files.
- *
- * This can be useful to find the smallest possible parquet file to load from the set in order to
- * discern schema information
+ * Create the Add action for a single file, with its statistics if they are available
  */
-fn find_smallest_file(files: &Vec<ObjectMeta>) -> Option<&ObjectMeta> {
-    if files.is_empty() {
-        return None;
+fn add_for(om: &ObjectMeta, stats: Option<String>, layout: &partitions::PartitionLayout) -> Add {
+    let partition_values = layout.partition_values_from(om.location.as_ref());
+
+    Add {
+        path: om.location.to_string(),
+        size: om.size as i64,
+        modification_time: om.last_modified.timestamp_millis(),
+        data_change: true,
+        deletion_vector: None,
+        stats,
+        stats_parsed: None,
+        tags: None,
+        partition_values,
+        partition_values_parsed: None,
     }
+}
 
-    let mut smallest = &files[0];
+/**
+ * Read the [ParquetMetaData] out of the footer of the given file
+ */
+pub async fn fetch_parquet_metadata(
+    file: &ObjectMeta,
+    store: Arc<DeltaObjectStore>,
+) -> OxbowResult<Arc<ParquetMetaData>> {
+    let mut reader = ParquetObjectReader::new(store, file.clone());
+    Ok(reader.get_metadata().await?)
+}
+
+/**
+ * Compute the JSON statistics string for an Add action from the file's [ParquetMetaData]
+ *
+ * The heavy lifting of aggregating row group statistics is left to deltalake's writer, which
+ * expects the thrift representation of the footer.
+ */
+pub(crate) fn stats_for(
+    location: &Path,
+    partition_values: &HashMap<String, Option<String>>,
+    metadata: &ParquetMetaData,
+) -> Option<String> {
+    use deltalake::parquet::format::FileMetaData;
+    use deltalake::parquet::schema::types::to_thrift;
+
+    let file_metadata = metadata.file_metadata();
+    let schema = match to_thrift(file_metadata.schema()) {
+        Ok(schema) => schema,
+        Err(err) => {
+            warn!("Failed to convert the Parquet schema of {location} for statistics: {err:?}");
+            return None;
+        }
+    };
+    let thrift = FileMetaData::new(
+        file_metadata.version(),
+        schema,
+        file_metadata.num_rows(),
+        metadata
+            .row_groups()
+            .iter()
+            .map(|rg| rg.to_thrift())
+            .collect::<Vec<_>>(),
+        file_metadata.key_value_metadata().cloned(),
+        file_metadata.created_by().map(|c| c.to_string()),
+        None,
+        None,
+        None,
+    );
+
+    match deltalake::writer::create_add(partition_values, location.to_string(), 0, &thrift) {
+        Ok(add) => add.stats,
+        Err(err) => {
+            warn!("Failed to compute statistics for {location}: {err:?}");
+            None
+        }
+    }
+}
+
+/// Provide a series of Remove actions for the given [ObjectMeta] entries, reading their partition
+/// values with the [ConversionOptions::partition_layout]
+pub fn remove_actions_for(files: &[ObjectMeta], options: &ConversionOptions) -> Vec<Action> {
+    files
+        .iter()
+        .map(|om| Remove {
+            path: om.location.to_string(),
+            data_change: true,
+            size: Some(om.size as i64),
+            partition_values: Some(
+                options
+                    .partition_layout
+                    .partition_values_from(om.location.as_ref()),
+            ),
+            ..Default::default()
+        })
+        .map(Action::remove)
+        .collect()
+}
+
+/**
+ * Return the smallest file from the given set of files.
+ *
+ * This can be useful to find the smallest possible parquet file to load from the set in order to
+ * discern schema information
+ */
+fn find_smallest_file(files: &[ObjectMeta]) -> Option<&ObjectMeta> {
+    if files.is_empty() {
+        return None;
+    }
+
+    let mut smallest = &files[0];
     for file in files.iter() {
         if file.size < smallest.size {
             smallest = file;
@@ -351,12 +1070,11 @@ mod tests {
     use super::*;
 
     use chrono::prelude::Utc;
-    use deltalake::Path;
 
     /*
      * test utilities to share between test cases
      */
-    mod util {
+    pub(crate) mod util {
         use std::collections::HashSet;
         use std::hash::Hash;
         use std::sync::Arc;
@@ -412,7 +1130,42 @@ mod tests {
                 .expect("Failed to remove temp _delta_log/");
 
             let url = Url::from_file_path(dir.path()).expect("Failed to parse local path");
-            (dir, object_store_for(&url, None))
+            (
+                dir,
+                object_store_for(&url, None).expect("Failed to make store"),
+            )
+        }
+
+        /**
+         * Write the given [RecordBatch] out as a parquet file at the relative path inside of the
+         * directory, creating any parent directories along the way
+         */
+        pub(crate) fn write_parquet(
+            dir: &std::path::Path,
+            relative: &str,
+            batch: &deltalake::arrow::record_batch::RecordBatch,
+        ) {
+            use deltalake::parquet::arrow::ArrowWriter;
+
+            let path = dir.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directories");
+            let file = std::fs::File::create(path).expect("Failed to create parquet file");
+            let mut writer =
+                ArrowWriter::try_new(file, batch.schema(), None).expect("Failed to create writer");
+            writer.write(batch).expect("Failed to write batch");
+            writer.close().expect("Failed to close writer");
+        }
+
+        /**
+         * Create an empty temporary directory and its [DeltaObjectStore]
+         */
+        pub(crate) fn create_empty_temp_path() -> (tempfile::TempDir, Arc<DeltaObjectStore>) {
+            let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
+            let url = Url::from_file_path(dir.path()).expect("Failed to parse local path");
+            (
+                dir,
+                object_store_for(&url, None).expect("Failed to make store"),
+            )
         }
     }
 
@@ -420,7 +1173,7 @@ mod tests {
     async fn discover_parquet_files_empty_dir() {
         let dir = tempfile::tempdir().expect("Failed to create a temporary directory");
         let url = Url::from_file_path(dir.path()).expect("Failed to parse local path");
-        let store = object_store_for(&url, None);
+        let store = object_store_for(&url, None).expect("Failed to make store");
 
         let files = discover_parquet_files(store.clone())
             .await
@@ -433,7 +1186,7 @@ mod tests {
         let path = std::fs::canonicalize("../../tests/data/hive/deltatbl-non-partitioned")
             .expect("Failed to canonicalize");
         let url = Url::from_file_path(path).expect("Failed to parse local path");
-        let store = object_store_for(&url, None);
+        let store = object_store_for(&url, None).expect("Failed to make store");
 
         let files = discover_parquet_files(store.clone())
             .await
@@ -474,14 +1227,19 @@ mod tests {
         }
     }
 
-    #[test]
-    fn add_actions_for_empty() {
-        let result = add_actions_for(&[]);
+    #[tokio::test]
+    async fn add_actions_for_empty() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let options = ConversionOptions::default();
+        let result = add_actions_for(&[], &options.footer_cache(store), &options).await;
         assert_eq!(0, result.len());
     }
 
-    #[test]
-    fn add_actions_for_not_empty() {
+    #[tokio::test]
+    async fn add_actions_for_not_empty() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
         let files = vec![ObjectMeta {
             location: Path::from(
                 "part-00001-f2126b8d-1594-451b-9c89-c4c2481bfd93-c000.snappy.parquet",
@@ -490,10 +1248,65 @@ mod tests {
             size: 689,
             e_tag: None,
         }];
-        let result = add_actions_for(&files);
+        let options = ConversionOptions::default();
+        let result = add_actions_for(&files, &options.footer_cache(store), &options).await;
         assert_eq!(1, result.len());
     }
 
+    #[tokio::test]
+    async fn add_actions_for_partitioned() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let options = ConversionOptions::default();
+        let actions = add_actions_for(&files, &options.footer_cache(store), &options).await;
+        assert_eq!(files.len(), actions.len());
+
+        for action in actions {
+            if let Action::add(add) = action {
+                let stats = add
+                    .get_stats()
+                    .expect("Failed to parse stats")
+                    .expect("Expected stats on the add action");
+                assert!(stats.num_records > 0, "Expected a record count");
+                assert!(
+                    !stats.min_values.contains_key("c2"),
+                    "Partition columns should not have statistics"
+                );
+                assert!(stats.min_values.contains_key("c1"));
+                assert!(stats.max_values.contains_key("c1"));
+                assert!(stats.null_count.contains_key("c1"));
+            } else {
+                panic!("Expected only add actions: {action:?}");
+            }
+        }
+    }
+
+    /*
+     * A missing or otherwise unreadable file should still be added, just without statistics
+     */
+    #[tokio::test]
+    async fn add_actions_for_unreadable_file() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = vec![ObjectMeta {
+            location: Path::from("c2=foo0/non-existent.parquet"),
+            last_modified: Utc::now(),
+            size: 689,
+            e_tag: None,
+        }];
+
+        let options = ConversionOptions::default();
+        let actions = add_actions_for(&files, &options.footer_cache(store), &options).await;
+        assert_eq!(1, actions.len());
+        if let Action::add(add) = &actions[0] {
+            assert_eq!(None, add.stats);
+        }
+    }
+
     #[test]
     fn remove_actions_for_not_empty() {
         let files = vec![ObjectMeta {
@@ -504,14 +1317,17 @@ mod tests {
             size: 689,
             e_tag: None,
         }];
-        let result = remove_actions_for(&files);
+        let result = remove_actions_for(&files, &ConversionOptions::default());
         assert_eq!(1, result.len());
     }
 
     #[test]
     fn partition_columns_from_empty() {
         let expected: Vec<String> = vec![];
-        assert_eq!(expected, partition_columns_from(&[]));
+        assert_eq!(
+            expected,
+            partitions::PartitionLayout::Hive.columns_from(&[])
+        );
     }
 
     #[test]
@@ -531,7 +1347,10 @@ mod tests {
                 e_tag: None,
             },
         ];
-        assert_eq!(expected, partition_columns_from(&files));
+        assert_eq!(
+            expected,
+            partitions::PartitionLayout::Hive.columns_from(&files)
+        );
     }
 
     #[test]
@@ -552,7 +1371,10 @@ mod tests {
                 size: 1024,
             },
         ];
-        assert_eq!(expected, partition_columns_from(&files));
+        assert_eq!(
+            expected,
+            partitions::PartitionLayout::Hive.columns_from(&files)
+        );
     }
 
     #[test]
@@ -573,67 +1395,1109 @@ mod tests {
                 size: 1024,
             },
         ];
-        util::assert_unordered_eq(&expected, &partition_columns_from(&files));
+        util::assert_unordered_eq(
+            &expected,
+            &partitions::PartitionLayout::Hive.columns_from(&files),
+        );
+    }
+
+    /*
+     * See <https://github.com/buoyant-data/oxbow/issues/2>
+     */
+    #[tokio::test]
+    async fn create_schema_for_partitioned_path() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(files.len(), 4, "No files discovered");
+
+        let parts = partitions::PartitionLayout::Hive.columns_from(&files);
+        assert_eq!(
+            parts.len(),
+            1,
+            "Expected to find one partition in the files"
+        );
+
+        let table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        assert!(
+            schema.get_field_with_name("c2").is_ok(),
+            "The schema does not include the expected partition key `c2`"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_table_with_stats() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+
+        let adds = table.get_state().files();
+        assert_eq!(2, adds.len());
+        for add in adds {
+            let stats = add
+                .get_stats()
+                .expect("Failed to parse stats")
+                .expect("Expected stats to be committed");
+            assert!(stats.num_records > 0, "Expected a record count");
+        }
+    }
+
+    #[tokio::test]
+    async fn discover_parquet_files_ignores_job_artifacts() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let expected = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let original = tempdir.path().join(expected[0].location.as_ref());
+        for artifact in [
+            "deltatbl-non-partitioned/_temporary/0/part-0.parquet",
+            "deltatbl-non-partitioned/.spark-staging-1/part-0.parquet",
+            "deltatbl-non-partitioned/part-0-tid-1-uncommitted.parquet",
+        ] {
+            let target = tempdir.path().join(artifact);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::copy(&original, target).expect("Failed to copy artifact");
+        }
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        util::assert_unordered_eq(
+            &expected
+                .iter()
+                .map(|f| f.location.clone())
+                .collect::<Vec<_>>(),
+            &files.iter().map(|f| f.location.clone()).collect::<Vec<_>>(),
+        );
+    }
+
+    #[tokio::test]
+    async fn discover_parquet_files_by_content() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let expected = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let original = tempdir.path().join(expected[0].location.as_ref());
+        let root = tempdir.path().join("deltatbl-non-partitioned");
+        std::fs::copy(&original, root.join("glue.parq")).unwrap();
+        std::fs::copy(&original, root.join("firehose-output")).unwrap();
+        std::fs::write(root.join("notes.txt"), "not parquet").unwrap();
+
+        let options = ConversionOptions {
+            parquet_extensions: vec!["parquet".into(), "parq".into()],
+            ..Default::default()
+        };
+        let files = discover_parquet_files_with_options(store.clone(), &options)
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(expected.len() + 1, files.len());
+
+        let options = ConversionOptions {
+            sniff_content: true,
+            ..options
+        };
+        let files = discover_parquet_files_with_options(store.clone(), &options)
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(expected.len() + 2, files.len());
+        assert!(files
+            .iter()
+            .any(|f| f.location.as_ref().ends_with("firehose-output")));
+    }
+
+    #[tokio::test]
+    async fn discover_parquet_files_with_filters() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let all = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let options = ConversionOptions {
+            include: vec!["deltatbl-partition-prune/date=20180520/**".into()],
+            exclude: vec!["**/city=hz/**".into()],
+            ..Default::default()
+        };
+        let files = discover_parquet_files_with_options(store.clone(), &options)
+            .await
+            .expect("Failed to discover parquet files");
+        assert!(files.len() < all.len());
+        assert_eq!(1, files.len(), "Only date=20180520/city=bj should match");
+        assert!(files[0].location.as_ref().contains("city=bj"));
+
+        let options = ConversionOptions {
+            include: vec!["[invalid".into()],
+            ..Default::default()
+        };
+        let result = discover_parquet_files_with_options(store, &options).await;
+        assert!(matches!(result, Err(OxbowError::InvalidConfiguration(_))));
+    }
+
+    #[tokio::test]
+    async fn create_table_quarantining_bad_files() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let root = tempdir.path().join("deltatbl-non-partitioned");
+        std::fs::write(root.join("empty.parquet"), "").unwrap();
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let result = create_table_with(&files, store.clone()).await;
+        assert!(result.is_err(), "An empty file should not be committed");
+
+        let options = ConversionOptions {
+            quarantine: validate::Quarantine::Move,
+            ..Default::default()
+        };
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(files.len() - 1, table.get_files().len());
+        assert!(!root.join("empty.parquet").exists());
+        assert!(tempdir
+            .path()
+            .join("_quarantine/deltatbl-non-partitioned/empty.parquet")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn create_table_leaves_incompatible_files_in_place() {
+        use deltalake::arrow::array::{Int32Array, StringArray};
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let ints = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let strings = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Utf8,
+                false,
+            )])),
+            vec![Arc::new(StringArray::from(vec!["one"]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "a.parquet", &ints);
+        util::write_parquet(dir.path(), "b.parquet", &ints);
+        util::write_parquet(dir.path(), "strings.parquet", &strings);
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let options = ConversionOptions {
+            quarantine: validate::Quarantine::Move,
+            ..Default::default()
+        };
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(2, table.get_files().len());
+        assert!(dir.path().join("strings.parquet").exists());
+        assert!(!dir.path().join("_quarantine").exists());
+    }
+
+    #[tokio::test]
+    async fn append_quarantining_bad_files() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+
+        let bad = tempdir
+            .path()
+            .join("deltatbl-non-partitioned/corrupt.parquet");
+        std::fs::write(&bad, "PAR1 definitely not a footer PAR1").unwrap();
+        let bad = store
+            .head(&Path::from("deltatbl-non-partitioned/corrupt.parquet"))
+            .await
+            .unwrap();
+
+        let options = ConversionOptions {
+            quarantine: validate::Quarantine::Tag,
+            ..Default::default()
+        };
+        let version = append_to_table_with_options(&[bad], &mut table, &options)
+            .await
+            .expect("Failed to append");
+        assert_eq!(
+            0, version,
+            "No commit should be made for only invalid files"
+        );
+        assert!(tempdir
+            .path()
+            .join("deltatbl-non-partitioned/corrupt.parquet.quarantined")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn create_table_in_chunks() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert!(
+            files.len() > 2,
+            "The fixture needs more than one chunk of files"
+        );
+
+        let options = ConversionOptions {
+            max_files_per_commit: Some(2),
+            checkpoint_after_conversion: true,
+            ..Default::default()
+        };
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+
+        let expected_version = (files.len() as i64 + 1) / 2 - 1;
+        assert_eq!(expected_version, table.version());
+        assert_eq!(files.len(), table.get_files().len());
+        util::assert_unordered_eq(
+            &["date".to_string(), "city".to_string()],
+            table.get_metadata().unwrap().partition_columns.as_slice(),
+        );
+
+        let checkpoint = store.head(&Path::from("_delta_log/_last_checkpoint")).await;
+        assert!(checkpoint.is_ok(), "Expected a checkpoint to be written");
+    }
+
+    #[tokio::test]
+    async fn create_table_in_chunks_checkpoints_on_the_interval() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert!(files.len() > 2, "The fixture needs at least three chunks");
+
+        let options = ConversionOptions {
+            max_files_per_commit: Some(1),
+            checkpoint_interval: Some(2),
+            ..Default::default()
+        };
+        create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+
+        let checkpoint = store
+            .head(&Path::from(
+                "_delta_log/00000000000000000002.checkpoint.parquet",
+            ))
+            .await;
+        assert!(checkpoint.is_ok(), "Expected a checkpoint between chunks");
+    }
+
+    #[tokio::test]
+    async fn create_table_over_an_existing_table() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        create_table_with(&files[0..1], store.clone())
+            .await
+            .expect("Failed to create table");
+        let table = create_table_with(&files[1..], store.clone())
+            .await
+            .expect("Expected the existing table to be returned");
+        assert_eq!(0, table.version());
+        assert_eq!(1, table.get_files().len());
+
+        let table = deltalake::open_table(store.root_uri())
+            .await
+            .expect("Failed to open the table");
+        assert_eq!(0, table.version());
+        assert_eq!(1, table.get_files().len());
+    }
+
+    /*
+     * The table schema should include columns which only exist in some of the files, regardless
+     * of which file happens to be the smallest
+     */
+    #[tokio::test]
+    async fn create_table_with_unified_schema() {
+        use deltalake::arrow::array::{Int32Array, Int64Array, StringArray};
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let small = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let large = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("name", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![2, 3, 4, 5])),
+                Arc::new(StringArray::from(vec!["b", "c", "d", "e"])),
+            ],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "small.parquet", &small);
+        util::write_parquet(dir.path(), "large.parquet", &large);
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        let name = schema
+            .get_field_with_name("name")
+            .expect("The schema should include the `name` column");
+        assert!(name.is_nullable());
+        assert_eq!(
+            &SchemaDataType::primitive("long".into()),
+            schema.get_field_with_name("id").unwrap().get_type()
+        );
+    }
+
+    #[tokio::test]
+    async fn create_table_with_incompatible_schemas() {
+        use deltalake::arrow::array::{Int32Array, StringArray};
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let ints = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let strings = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Utf8,
+                false,
+            )])),
+            vec![Arc::new(StringArray::from(vec!["one"]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "ints.parquet", &ints);
+        util::write_parquet(dir.path(), "strings.parquet", &strings);
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let result = create_table_with(&files, store.clone()).await;
+        assert!(
+            result.is_err(),
+            "Should not create a table from incompatible files"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_table_with_escaped_partitions() {
+        let (tempdir, store) = util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-special-chars-in-partition-column",
+        );
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(4, files.len());
+
+        let table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        assert_eq!(
+            &SchemaDataType::primitive("string".into()),
+            schema.get_field_with_name("c2").unwrap().get_type()
+        );
+
+        let tracked = table.get_file_set();
+        for file in files.iter() {
+            assert!(tracked.contains(&file.location), "{file:?} is not tracked");
+        }
+        for add in table.get_state().files() {
+            let value = add.partition_values.get("c2").cloned().flatten();
+            assert!(matches!(value.as_deref(), Some("+ =%0") | Some("+ =%1")));
+        }
+
+        // The paths in the log decode into the object keys, the same as those Spark wrote below
+        // the fixture's own table root
+        let log_paths = |log: &str| -> Vec<String> {
+            let mut paths: Vec<String> = log
+                .lines()
+                .filter_map(|line| {
+                    let action: serde_json::Value = serde_json::from_str(line).ok()?;
+                    let path = action.get("add")?.get("path")?.as_str()?;
+                    Some(
+                        deltalake::Path::from_url_path(path)
+                            .expect("Failed to decode the path")
+                            .to_string(),
+                    )
+                })
+                .collect();
+            paths.sort();
+            paths
+        };
+        let log =
+            std::fs::read_to_string(tempdir.path().join("_delta_log/00000000000000000000.json"))
+                .unwrap();
+        let fixture = std::fs::read_to_string(
+            "../../tests/data/hive/deltatbl-special-chars-in-partition-column/_delta_log/00000000000000000000.json",
+        )
+        .unwrap();
+        let expected: Vec<String> = log_paths(&fixture)
+            .iter()
+            .map(|path| format!("deltatbl-special-chars-in-partition-column/{path}"))
+            .collect();
+        assert_eq!(4, expected.len());
+        assert_eq!(expected, log_paths(&log));
+
+        // Checkpoints hold the same encoded paths as the commits. deltalake does not decode the
+        // paths it reads from a checkpoint, unlike those from a commit, so they are decoded here
+        deltalake::checkpoints::create_checkpoint(&table)
+            .await
+            .expect("Failed to create a checkpoint");
+        std::fs::remove_file(tempdir.path().join("_delta_log/00000000000000000000.json"))
+            .expect("Failed to remove the commit");
+        let reopened = deltalake::open_table(tempdir.path().to_str().unwrap())
+            .await
+            .expect("Failed to open the table");
+        let mut checkpointed: Vec<String> = reopened
+            .get_state()
+            .files()
+            .iter()
+            .map(|add| {
+                deltalake::Path::from_url_path(&add.path)
+                    .expect("Failed to decode the path")
+                    .to_string()
+            })
+            .collect();
+        checkpointed.sort();
+        assert_eq!(expected, checkpointed);
+    }
+
+    #[tokio::test]
+    async fn create_table_with_typed_partitions() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let options = ConversionOptions {
+            infer_partition_types: true,
+            ..Default::default()
+        };
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        let field = schema
+            .get_field_with_name("date")
+            .expect("The schema should include the partition column");
+        assert_eq!(
+            &SchemaDataType::primitive("integer".into()),
+            field.get_type()
+        );
+    }
+
+    #[tokio::test]
+    async fn create_table_with_string_partitions() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        for column in ["date", "city"] {
+            assert_eq!(
+                &SchemaDataType::primitive("string".into()),
+                schema.get_field_with_name(column).unwrap().get_type()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn append_skips_values_not_of_the_partition_type() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            infer_partition_types: true,
+            ..Default::default()
+        };
+        let mut table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        let version = table.version();
+
+        // A later job writes a non-numeric value into the integer `date` column
+        let source = tempdir.path().join(files[0].location.as_ref());
+        let latest = tempdir
+            .path()
+            .join("deltatbl-partition-prune/date=latest/city=sh/part-00000.snappy.parquet");
+        std::fs::create_dir_all(latest.parent().unwrap()).unwrap();
+        std::fs::copy(source, latest).unwrap();
+        let latest = store
+            .head(&Path::from(
+                "deltatbl-partition-prune/date=latest/city=sh/part-00000.snappy.parquet",
+            ))
+            .await
+            .expect("Failed to find the new file");
+
+        let appended = append_to_table_with_options(&[latest], &mut table, &options)
+            .await
+            .expect("Failed to append files");
+        assert_eq!(version, appended, "The file should not have been committed");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(files.len(), table.get_files().len());
+    }
+
+    #[tokio::test]
+    async fn create_table_with_properties() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            table_properties: HashMap::from([
+                ("delta.appendOnly".to_string(), "true".to_string()),
+                ("delta.checkpointInterval".to_string(), "5".to_string()),
+            ]),
+            table_name: Some("events".into()),
+            table_description: Some("Events exported from the warehouse".into()),
+            ..Default::default()
+        };
+
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        let metadata = table.get_metadata().expect("Failed to get metadata");
+        assert_eq!(Some("events".to_string()), metadata.name);
+        assert_eq!(
+            Some("Events exported from the warehouse".to_string()),
+            metadata.description
+        );
+        assert_eq!(
+            Some(&Some("true".to_string())),
+            metadata.configuration.get("delta.appendOnly")
+        );
+        assert_eq!(
+            Some(&Some("5".to_string())),
+            metadata.configuration.get("delta.checkpointInterval")
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_from_append_only_table() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            table_properties: HashMap::from([("delta.appendOnly".to_string(), "true".to_string())]),
+            ..Default::default()
+        };
+        let mut table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+
+        let result = remove_from_table_with_options(&files[..1], &mut table, &options).await;
+        assert!(
+            matches!(result, Err(OxbowError::AppendOnlyTable { count: 1, .. })),
+            "Expected the removal to be refused, got {result:?}"
+        );
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(0, table.version());
+        assert_eq!(files.len(), table.get_files().len());
+    }
+
+    #[tokio::test]
+    async fn create_table_with_invalid_properties() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            table_properties: HashMap::from([(
+                "delta.appendOnly".to_string(),
+                "sometimes".to_string(),
+            )]),
+            ..Default::default()
+        };
+
+        let result = create_table_with_options(&files, store.clone(), &options).await;
+        assert!(matches!(result, Err(OxbowError::InvalidConfiguration(_))));
+    }
+
+    #[tokio::test]
+    async fn commits_record_lineage() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut options = ConversionOptions::default();
+        options
+            .commit_metadata
+            .insert("sourceBucket".into(), serde_json::Value::from("my-bucket"));
+
+        let mut table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        remove_from_table_with_options(&files[..1], &mut table, &options)
+            .await
+            .expect("Failed to remove file");
+
+        let history = table.history(None).await.expect("Failed to read history");
+        assert_eq!(2, history.len());
+        for ((commit, file_count), operation) in history
+            .iter()
+            .zip([files.len(), 1])
+            .zip(["CREATE TABLE", "DELETE"])
+        {
+            assert_eq!(Some(operation.to_string()), commit.operation);
+            assert!(commit.operation_parameters.is_some());
+            assert_eq!(Some(lineage::ENGINE_INFO.to_string()), commit.engine_info);
+            assert!(commit.is_blind_append.is_some());
+            assert!(commit.info.contains_key("clientVersion"));
+            assert_eq!(
+                Some(&serde_json::Value::from(lineage::OXBOW_VERSION)),
+                commit.info.get("oxbowVersion")
+            );
+            assert_eq!(
+                Some(&serde_json::Value::from(file_count)),
+                commit.info.get("oxbowFileCount")
+            );
+            assert_eq!(
+                Some(&serde_json::Value::from("my-bucket")),
+                commit.info.get("sourceBucket")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn create_table_with_partition_type_override() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            partition_types: HashMap::from([("date".to_string(), "string".to_string())]),
+            infer_partition_types: true,
+            ..Default::default()
+        };
+
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        let schema = table.get_schema().expect("Failed to get schema");
+        let field = schema
+            .get_field_with_name("date")
+            .expect("The schema should include the partition column");
+        assert_eq!(
+            &SchemaDataType::primitive("string".into()),
+            field.get_type()
+        );
+    }
+
+    #[tokio::test]
+    async fn create_table_with_partition_template() {
+        use deltalake::arrow::array::Int32Array;
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "2023/10/17/13/a.parquet", &batch);
+        util::write_parquet(dir.path(), "2023/10/17/14/b.parquet", &batch);
+
+        let options = ConversionOptions {
+            partition_layout: "{year}/{month}/{day}/{hour}".parse().unwrap(),
+            ..Default::default()
+        };
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+
+        let metadata = table.get_metadata().expect("Failed to get metadata");
+        assert_eq!(
+            vec!["year", "month", "day", "hour"],
+            metadata.partition_columns
+        );
+        let add = table
+            .get_state()
+            .files()
+            .iter()
+            .find(|add| add.path.ends_with("a.parquet"))
+            .expect("Failed to find the add")
+            .clone();
+        assert_eq!(
+            Some(&Some("13".to_string())),
+            add.partition_values.get("hour")
+        );
+
+        let removed = files
+            .iter()
+            .filter(|f| f.location.as_ref().ends_with("a.parquet"))
+            .cloned()
+            .collect::<Vec<_>>();
+        let actions = remove_actions_for(&removed, &options);
+        match &actions[0] {
+            Action::remove(remove) => assert_eq!(
+                Some(&Some("2023".to_string())),
+                remove.partition_values.as_ref().unwrap().get("year")
+            ),
+            _ => panic!("Expected a remove action"),
+        }
+        remove_from_table_with_options(&removed, &mut table, &options)
+            .await
+            .expect("Failed to remove from table");
+    }
+
+    #[tokio::test]
+    async fn create_table_skips_files_outside_the_partition_template() {
+        use deltalake::arrow::array::Int32Array;
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "2023/10/17/13/a.parquet", &batch);
+        util::write_parquet(dir.path(), "stray.parquet", &batch);
+
+        let options = ConversionOptions {
+            partition_layout: "{year}/{month}/{day}/{hour}".parse().unwrap(),
+            ..Default::default()
+        };
+        let discovered = discover_parquet_files_with_options(store.clone(), &options)
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(1, discovered.len());
+
+        // Files passed in directly are skipped the same way
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(2, files.len());
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(
+            vec!["2023/10/17/13/a.parquet".to_string()],
+            table
+                .get_files_iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /*
+     * See <https://github.com/buoyant-data/oxbow/issues/5>
+     */
+    #[tokio::test]
+    async fn create_table_without_files() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = vec![];
+
+        let result = create_table_with(&files, store.clone()).await;
+        assert!(result.is_err());
+    }
+
+    /*
+     * Attempt to create a table with a parquet file containing a timestamp column with millisecond
+     * precision
+     */
+    #[tokio::test]
+    async fn create_table_with_millis_timestamp() {
+        let (_tempdir, store) = util::create_temp_path_with("../../tests/data/hive/faker_products");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(files.len(), 1, "No files discovered");
+
+        let result = create_table_with(&files, store.clone()).await;
+        assert!(result.is_ok(), "Failed to create: {result:?}");
+    }
+
+    #[tokio::test]
+    async fn create_table_with_rewritten_timestamps() {
+        let (tempdir, store) = util::create_temp_path_with("../../tests/data/hive/faker_products");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            timestamp_rewrite: rewrite::TimestampRewrite::KeepOriginals,
+            ..Default::default()
+        };
+
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        let committed = table.get_files();
+        assert_eq!(1, committed.len());
+        assert_eq!(
+            Path::from("faker_products/1701800282197_0.micros.parquet"),
+            committed[0],
+            "The rewritten file should have been committed"
+        );
+        assert!(
+            tempdir
+                .path()
+                .join("faker_products/1701800282197_0.parquet")
+                .exists(),
+            "The original file should have been kept"
+        );
+        let report = doctor::check_table(&table, &options)
+            .await
+            .expect("Failed to check the table");
+        assert!(report.is_healthy(), "Unhealthy table: {report:?}");
+
+        // Converting the location again finds the rewritten file in place of the original
+        std::fs::remove_dir_all(tempdir.path().join("_delta_log"))
+            .expect("Failed to remove the log");
+        let discovered = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(
+            committed,
+            discovered
+                .iter()
+                .map(|f| f.location.clone())
+                .collect::<Vec<_>>()
+        );
+
+        // Given both the original and its rewritten copy, the copy is only added once
+        let mut both = files.clone();
+        both.extend(discovered);
+        let table = create_table_with_options(&both, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(committed, table.get_files());
+
+        let schema = options
+            .footer_cache(store.clone())
+            .schema(&store.head(&committed[0]).await.unwrap())
+            .await
+            .expect("Failed to read the rewritten schema");
+        for field in schema.fields() {
+            if let deltalake::arrow::datatypes::DataType::Timestamp(unit, _) = field.data_type() {
+                assert_eq!(&deltalake::arrow::datatypes::TimeUnit::Microsecond, unit);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn create_table_with_rewritten_timestamps_removing_originals() {
+        let (tempdir, store) = util::create_temp_path_with("../../tests/data/hive/faker_products");
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            timestamp_rewrite: rewrite::TimestampRewrite::RemoveOriginals,
+            ..Default::default()
+        };
+
+        let table = create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(1, table.get_files().len());
+        assert!(
+            !tempdir
+                .path()
+                .join("faker_products/1701800282197_0.parquet")
+                .exists(),
+            "The original file should have been removed"
+        );
+        assert!(tempdir
+            .path()
+            .join("faker_products/1701800282197_0.micros.parquet")
+            .exists());
     }
 
-    /*
-     * See <https://github.com/buoyant-data/oxbow/issues/2>
-     */
     #[tokio::test]
-    async fn create_schema_for_partitioned_path() {
+    async fn create_table_without_timestamps_to_rewrite() {
         let (_tempdir, store) =
-            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-non-partitioned");
         let files = discover_parquet_files(store.clone())
             .await
             .expect("Failed to discover parquet files");
-        assert_eq!(files.len(), 4, "No files discovered");
-
-        let parts = partition_columns_from(&files);
-        assert_eq!(
-            parts.len(),
-            1,
-            "Expected to find one partition in the files"
-        );
+        let options = ConversionOptions {
+            timestamp_rewrite: rewrite::TimestampRewrite::RemoveOriginals,
+            ..Default::default()
+        };
 
-        let table = create_table_with(&files, store.clone())
+        let table = create_table_with_options(&files, store.clone(), &options)
             .await
             .expect("Failed to create table");
-        let schema = table.get_schema().expect("Failed to get schema");
-        assert!(
-            schema.get_field_with_name("c2").is_ok(),
-            "The schema does not include the expected partition key `c2`"
-        );
+        let mut committed = table.get_files();
+        committed.sort();
+        let mut expected: Vec<Path> = files.into_iter().map(|f| f.location).collect();
+        expected.sort();
+        assert_eq!(expected, committed);
+    }
+
+    #[test]
+    fn invalid_location_url() {
+        let result = location_url("./this/does/not/exist");
+        assert!(matches!(result, Err(OxbowError::InvalidLocation { .. })));
+    }
+
+    #[test]
+    fn unknown_store_scheme() {
+        let url = Url::parse("unknown://bucket/table").unwrap();
+        let result = object_store_for(&url, None);
+        assert!(matches!(result, Err(OxbowError::StoreConstruction { .. })));
     }
 
-    /*
-     * See <https://github.com/buoyant-data/oxbow/issues/5>
-     */
     #[tokio::test]
-    async fn create_table_without_files() {
-        let (_tempdir, store) =
-            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+    async fn create_with_unsupported_types() {
+        use deltalake::arrow::array::Time32MillisecondArray;
+        use deltalake::arrow::datatypes::{DataType, Field, TimeUnit};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, _store) = util::create_empty_temp_path();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "time",
+            DataType::Time32(TimeUnit::Millisecond),
+            true,
+        )]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Time32MillisecondArray::from(vec![1, 2]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "times.parquet", &batch);
+
+        let result = convert(dir.path().to_str().unwrap(), None).await;
+        assert!(matches!(result, Err(OxbowError::UnsupportedTypes { .. })));
+    }
 
-        let files = vec![];
+    #[tokio::test]
+    async fn plan_partitioned_location() {
+        let (tempdir, _store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partition-prune");
+        let location = tempdir.path().to_str().unwrap();
 
-        let result = create_table_with(&files, store.clone()).await;
-        assert!(result.is_err());
+        let plan = plan(location, None, &ConversionOptions::default())
+            .await
+            .expect("Failed to plan");
+        assert_eq!(None, plan.existing_version);
+        assert!(!plan.files.is_empty(), "Expected files to be planned");
+        assert!(plan.skipped.is_empty());
+        util::assert_unordered_eq(
+            &["date".to_string(), "city".to_string()],
+            &plan.partition_columns,
+        );
+        let schema = plan.schema.expect("Expected a schema to be planned");
+        assert_eq!(
+            &SchemaDataType::primitive("string".into()),
+            schema.get_field_with_name("date").unwrap().get_type()
+        );
+
+        // Planning must not have created a table
+        assert!(deltalake::open_table(location).await.is_err());
+        serde_json::to_string(&plan.files).expect("Failed to serialize the plan");
     }
 
-    /*
-     * Attempt to create a table with a parquet file containing a timestamp column with millisecond
-     * precision
-     */
     #[tokio::test]
-    async fn create_table_with_millis_timestamp() {
-        let (_tempdir, store) = util::create_temp_path_with("../../tests/data/hive/faker_products");
+    async fn plan_with_coercions() {
+        let (tempdir, _store) = util::create_temp_path_with("../../tests/data/hive/faker_products");
+        let location = tempdir.path().to_str().unwrap();
 
-        let files = discover_parquet_files(store.clone())
+        let plan = plan(location, None, &ConversionOptions::default())
             .await
-            .expect("Failed to discover parquet files");
-        assert_eq!(files.len(), 1, "No files discovered");
+            .expect("Failed to plan");
+        assert!(
+            !plan.coercions.is_empty(),
+            "Expected the millisecond timestamps to be coerced"
+        );
+        let json = serde_json::to_value(&plan).expect("Failed to serialize the plan");
+        assert!(json["coercions"][0]["from"].is_string());
+    }
 
-        let result = create_table_with(&files, store.clone()).await;
-        assert!(result.is_ok(), "Failed to create: {result:?}");
+    #[tokio::test]
+    async fn plan_existing_table() {
+        let location = std::fs::canonicalize("../../tests/data/hive/deltatbl-partitioned")
+            .expect("Failed to canonicalize");
+
+        let plan = plan(
+            location.to_str().unwrap(),
+            None,
+            &ConversionOptions::default(),
+        )
+        .await
+        .expect("Failed to plan");
+        assert_eq!(Some(0), plan.existing_version);
+        assert!(plan.files.is_empty());
     }
 
     #[tokio::test]
@@ -642,7 +2506,8 @@ mod tests {
         std::env::set_var("AWS_REGION", "custom");
 
         let files: Vec<ObjectMeta> = vec![];
-        let store = object_store_for(&Url::parse("s3://example/non-existent").unwrap(), None);
+        let store = object_store_for(&Url::parse("s3://example/non-existent").unwrap(), None)
+            .expect("Failed to make store");
         let result = create_table_with(&files, store).await;
         println!("result from create_table_with: {:?}", result);
         assert!(result.is_err());
@@ -658,7 +2523,7 @@ mod tests {
             std::fs::canonicalize("../../tests/data/hive/deltatbl-non-partitioned-with-checkpoint")
                 .expect("Failed to canonicalize");
         let url = Url::from_file_path(test_dir).expect("Failed to parse local path");
-        let store = object_store_for(&url, None);
+        let store = object_store_for(&url, None).expect("Failed to make store");
 
         let files = discover_parquet_files(store.clone())
             .await
@@ -763,7 +2628,7 @@ mod tests {
 
         let mut uniq = HashSet::new();
         for field in &fields {
-            uniq.insert(field.clone());
+            uniq.insert(*field);
         }
         assert_eq!(
             uniq.len(),
@@ -772,6 +2637,162 @@ mod tests {
         );
     }
 
+    /*
+     * Write a file with a column the table does not have, and append it to a table created from
+     * the existing files
+     */
+    async fn append_file_with_new_column(options: &ConversionOptions) -> DeltaTable {
+        use deltalake::arrow::array::{Int32Array, StringArray};
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let original = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![Field::new(
+                "id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "original.parquet", &original);
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+
+        let evolved = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![2])),
+                Arc::new(StringArray::from(vec!["two"])),
+            ],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "evolved.parquet", &evolved);
+        let files: Vec<ObjectMeta> = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files")
+            .into_iter()
+            .filter(|f| f.location.as_ref() == "evolved.parquet")
+            .collect();
+
+        append_to_table_with_options(&files, &mut table, options)
+            .await
+            .expect("Failed to append files");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(1, table.version());
+        assert_eq!(2, table.get_files().len());
+        drop(dir);
+        table
+    }
+
+    #[tokio::test]
+    async fn create_table_with_column_mapping() {
+        use deltalake::arrow::array::{Float64Array, Int32Array};
+        use deltalake::arrow::datatypes::{DataType, Field};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (dir, store) = util::create_empty_temp_path();
+        let batch = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("total (usd)", DataType::Float64, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![1])),
+                Arc::new(Float64Array::from(vec![1.5])),
+            ],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "pandas.parquet", &batch);
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+
+        let mut table = create_table_with(&files, store.clone())
+            .await
+            .expect("Failed to create table");
+        assert_eq!(2, table.get_min_reader_version());
+        assert_eq!(5, table.get_min_writer_version());
+        assert_eq!(Some("name".to_string()), mapping::mode_of(&table));
+        let schema = table.get_schema().expect("Failed to get schema");
+        let total = schema.get_field_with_name("total (usd)").unwrap();
+        assert_eq!(
+            Some(&serde_json::Value::from("total (usd)")),
+            total.get_metadata().get(mapping::PHYSICAL_NAME_KEY)
+        );
+
+        let evolved = RecordBatch::try_new(
+            Arc::new(ArrowSchema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("tax (usd)", DataType::Float64, false),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![2])),
+                Arc::new(Float64Array::from(vec![0.5])),
+            ],
+        )
+        .unwrap();
+        util::write_parquet(dir.path(), "evolved.parquet", &evolved);
+        let files: Vec<ObjectMeta> = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files")
+            .into_iter()
+            .filter(|f| f.location.as_ref() == "evolved.parquet")
+            .collect();
+        let options = ConversionOptions {
+            schema_evolution: true,
+            ..Default::default()
+        };
+        append_to_table_with_options(&files, &mut table, &options)
+            .await
+            .expect("Failed to append files");
+        table.load().await.expect("Failed to reload the table");
+
+        let schema = table.get_schema().expect("Failed to get schema");
+        let tax = schema.get_field_with_name("tax (usd)").unwrap();
+        assert_eq!(
+            Some(&serde_json::Value::from(3)),
+            tax.get_metadata().get(mapping::ID_KEY)
+        );
+        let configuration = &table.get_metadata().unwrap().configuration;
+        assert_eq!(
+            Some(&Some("3".to_string())),
+            configuration.get(mapping::MAX_COLUMN_ID_KEY)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_append_with_schema_evolution() {
+        let options = ConversionOptions {
+            schema_evolution: true,
+            ..Default::default()
+        };
+        let table = append_file_with_new_column(&options).await;
+        let schema = table.get_schema().expect("Failed to get schema");
+        let field = schema
+            .get_field_with_name("name")
+            .expect("The new column should have been added to the schema");
+        assert!(field.is_nullable(), "Evolved columns must be nullable");
+    }
+
+    #[tokio::test]
+    async fn test_append_without_schema_evolution() {
+        let table = append_file_with_new_column(&ConversionOptions::default()).await;
+        let schema = table.get_schema().expect("Failed to get schema");
+        assert!(
+            schema.get_field_with_name("name").is_err(),
+            "The schema should not change without schema evolution enabled"
+        );
+    }
+
     #[tokio::test]
     async fn test_remove_from_table() {
         let (_tempdir, store) =
@@ -815,7 +2836,7 @@ mod tests {
         assert_eq!(files.len(), 4, "No files discovered");
 
         // Only creating the table with some of the files
-        let mut table = create_table_with(&files[0..1].to_vec(), store.clone())
+        let mut table = create_table_with(&files[0..1], store.clone())
             .await
             .expect("Failed to create table");
         let schema = table.get_schema().expect("Failed to get schema");
@@ -838,6 +2859,319 @@ mod tests {
         assert_eq!(table.get_files().len(), 0, "Found redundant files!");
     }
 
+    #[tokio::test]
+    async fn test_remove_only_untracked_files() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files[0..1], store.clone())
+            .await
+            .expect("Failed to create table");
+
+        // Originals deleted after a rewrite were never committed, so nothing should be removed
+        let version = remove_from_table(&files[1..], &mut table)
+            .await
+            .expect("Failed to remove files");
+        assert_eq!(0, version, "Removing untracked files should not commit");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(0, table.version());
+        assert_eq!(1, table.get_files().len());
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_events_are_skipped() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files[1..], store.clone())
+            .await
+            .expect("Failed to create table");
+        let file = &files[0..1];
+        let adds = |sequence| ConversionOptions {
+            transaction: Some(transactions::Transaction::for_adds(sequence)),
+            ..Default::default()
+        };
+        let removes = |sequence| ConversionOptions {
+            transaction: Some(transactions::Transaction::for_removes(sequence)),
+            ..Default::default()
+        };
+
+        // The file is added, removed and then added again by a later batch
+        append_to_table_with_options(file, &mut table, &adds(1))
+            .await
+            .expect("Failed to append files");
+        table.load().await.expect("Failed to reload the table");
+        remove_from_table_with_options(file, &mut table, &removes(2))
+            .await
+            .expect("Failed to remove files");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(2, table.version());
+        assert_eq!(3, table.get_files().len());
+
+        // Redelivering the batches which have already been applied must not change the table
+        append_to_table_with_options(file, &mut table, &adds(1))
+            .await
+            .expect("Failed to append files");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(2, table.version(), "The added batch was applied twice");
+
+        append_to_table_with_options(file, &mut table, &adds(3))
+            .await
+            .expect("Failed to append files");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(3, table.version());
+        assert_eq!(4, table.get_files().len());
+
+        remove_from_table_with_options(file, &mut table, &removes(2))
+            .await
+            .expect("Failed to remove files");
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(3, table.version(), "The removed batch was applied twice");
+        assert_eq!(4, table.get_files().len());
+    }
+
+    #[tokio::test]
+    async fn test_batches_applied_by_other_writers_are_skipped() {
+        let (tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files[1..], store.clone())
+            .await
+            .expect("Failed to create table");
+        let mut stale = deltalake::open_table(tempdir.path().to_str().unwrap())
+            .await
+            .expect("Failed to open the table");
+        let options = ConversionOptions {
+            transaction: Some(transactions::Transaction::for_adds(1)),
+            ..Default::default()
+        };
+
+        // Another writer applies the batch and then removes its file again
+        append_to_table_with_options(&files[0..1], &mut table, &options)
+            .await
+            .expect("Failed to append files");
+        remove_from_table(&files[0..1], &mut table)
+            .await
+            .expect("Failed to remove files");
+
+        let version = append_to_table_with_options(&files[0..1], &mut stale, &options)
+            .await
+            .expect("Failed to append files");
+        assert_eq!(2, version, "The batch was applied again");
+        assert_eq!(files.len() - 1, stale.get_files().len());
+    }
+
+    #[tokio::test]
+    async fn test_transactions_are_kept_per_application() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let mut table = create_table_with(&files[files.len() - 1..], store.clone())
+            .await
+            .expect("Failed to create table");
+
+        // However many batches are committed the table holds a single txn for each application
+        for (sequence, file) in files[..files.len() - 1].chunks(1).enumerate() {
+            let options = ConversionOptions {
+                transaction: Some(transactions::Transaction::for_adds(sequence as i64 + 1)),
+                ..Default::default()
+            };
+            append_to_table_with_options(file, &mut table, &options)
+                .await
+                .expect("Failed to append files");
+            table.load().await.expect("Failed to reload the table");
+        }
+        assert_eq!(files.len(), table.get_files().len());
+        assert_eq!(
+            HashMap::from([(transactions::ADD_APP_ID.to_string(), files.len() as i64 - 1)]),
+            *table.get_app_transaction_version()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_interval() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            checkpoint_interval: Some(2),
+            table_properties: HashMap::from([(
+                "delta.checkpointInterval".to_string(),
+                "3".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let mut table = create_table_with_options(&files[2..], store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        assert_eq!(3, checkpoint::interval_for(&table, &options));
+
+        for file in files[0..2].chunks(1) {
+            append_to_table_with_options(file, &mut table, &options)
+                .await
+                .expect("Failed to append files");
+        }
+        let version = remove_from_table_with_options(&files[0..1], &mut table, &options)
+            .await
+            .expect("Failed to remove files");
+        assert_eq!(3, version);
+
+        // The table's interval wins over the configured one, and removes are checkpointed too
+        for (version, expected) in [(2, false), (3, true)] {
+            let checkpoint = store
+                .head(&Path::from(format!(
+                    "_delta_log/{version:020}.checkpoint.parquet"
+                )))
+                .await;
+            assert_eq!(
+                expected,
+                checkpoint.is_ok(),
+                "Checkpoint of version {version}"
+            );
+        }
+        assert_eq!(
+            3,
+            table.version(),
+            "The table should be left at its latest version"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_log_cleanup() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            expired_log_cleanup: true,
+            table_properties: HashMap::from([
+                ("delta.checkpointInterval".to_string(), "2".to_string()),
+                (
+                    "delta.logRetentionDuration".to_string(),
+                    "interval 0 seconds".to_string(),
+                ),
+            ]),
+            ..Default::default()
+        };
+        let mut table = create_table_with_options(&files[2..], store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        for file in files[0..2].chunks(1) {
+            append_to_table_with_options(file, &mut table, &options)
+                .await
+                .expect("Failed to append files");
+        }
+
+        let log_files = |version: i64| {
+            let store = store.clone();
+            async move {
+                store
+                    .head(&Path::from(format!("_delta_log/{version:020}.json")))
+                    .await
+                    .is_ok()
+            }
+        };
+        assert!(!log_files(0).await, "Version 0 should have been removed");
+        assert!(!log_files(1).await, "Version 1 should have been removed");
+        assert!(log_files(2).await, "The checkpointed version must be kept");
+
+        let mut table = deltalake::open_table(table.table_uri())
+            .await
+            .expect("Failed to open the table from its checkpoint");
+        assert_eq!(2, table.version());
+        assert_eq!(4, table.get_files().len());
+
+        let summary = checkpoint::checkpoint_version(&mut table, 2, &options)
+            .await
+            .expect("Failed to checkpoint");
+        assert_eq!(0, summary.deleted_log_files);
+    }
+
+    #[tokio::test]
+    async fn test_expired_log_cleanup_stops_at_the_checkpoint() {
+        let (_tempdir, store) =
+            util::create_temp_path_with("../../tests/data/hive/deltatbl-partitioned");
+
+        let files = discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            expired_log_cleanup: true,
+            table_properties: HashMap::from([
+                ("delta.checkpointInterval".to_string(), "100".to_string()),
+                (
+                    "delta.logRetentionDuration".to_string(),
+                    "interval 0 seconds".to_string(),
+                ),
+            ]),
+            ..Default::default()
+        };
+        let mut table = create_table_with_options(&files[2..], store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+        for file in files[0..2].chunks(1) {
+            append_to_table_with_options(file, &mut table, &options)
+                .await
+                .expect("Failed to append files");
+        }
+        table.load().await.expect("Failed to reload the table");
+        assert_eq!(2, table.version());
+
+        let log_files = |version: i64| {
+            let store = store.clone();
+            async move {
+                store
+                    .head(&Path::from(format!("_delta_log/{version:020}.json")))
+                    .await
+                    .is_ok()
+            }
+        };
+
+        // Every commit has expired, but without a checkpoint none of them can be removed
+        let deleted = checkpoint::cleanup_expired_logs(&table)
+            .await
+            .expect("Failed to clean up");
+        assert_eq!(0, deleted);
+        for version in 0..=2 {
+            assert!(log_files(version).await, "Version {version} was removed");
+        }
+
+        // The commits after the checkpoint have expired too, and must be kept
+        let summary = checkpoint::checkpoint_version(&mut table, 1, &options)
+            .await
+            .expect("Failed to checkpoint");
+        assert_eq!(1, summary.deleted_log_files);
+        assert!(!log_files(0).await, "Version 0 should have been removed");
+        assert!(log_files(1).await, "The checkpointed version must be kept");
+        assert!(
+            log_files(2).await,
+            "Versions after the checkpoint must be kept"
+        );
+
+        let table = deltalake::open_table(table.table_uri())
+            .await
+            .expect("Failed to open the table from its checkpoint");
+        assert_eq!(2, table.version());
+        assert_eq!(4, table.get_files().len());
+    }
+
     #[tokio::test]
     async fn test_remove_empty_set() {
         let (_tempdir, store) =
@@ -858,7 +3192,7 @@ mod tests {
         );
         assert_eq!(0, table.version(), "Unexpected version");
 
-        remove_from_table(&vec![], &mut table)
+        remove_from_table(&[], &mut table)
             .await
             .expect("Failed to append files");
         table.load().await.expect("Failed to reload the table");
@@ -0,0 +1,131 @@
+/*
+ * The lineage module builds the application metadata oxbow records in the `commitInfo` of every
+ * commit, so that the table history shows what produced each version
+ */
+use chrono::prelude::*;
+use deltalake::protocol::{Action, DeltaOperation, SaveMode};
+use serde_json::{Map, Value};
+
+use crate::ConversionOptions;
+
+/// The version of oxbow making the commits
+pub const OXBOW_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The `engineInfo` recorded in the commitInfo, following the `<engine>/<version>` convention
+pub const ENGINE_INFO: &str = concat!("oxbow/", env!("CARGO_PKG_VERSION"));
+
+/// The keys of the typed commitInfo fields, which custom metadata cannot be recorded under
+const RESERVED_KEYS: &[&str] = &[
+    "timestamp",
+    "userId",
+    "userName",
+    "operation",
+    "operationParameters",
+    "readVersion",
+    "isolationLevel",
+    "isBlindAppend",
+    "engineInfo",
+];
+
+/**
+ * Return the application metadata for a commit of `file_count` files, which is recorded next to
+ * the typed fields of the commitInfo.
+ *
+ * The keys in [ConversionOptions::commit_metadata] are included as they are, except that they
+ * cannot replace the typed fields or the keys oxbow records itself.
+ */
+pub fn commit_metadata(options: &ConversionOptions, file_count: usize) -> Map<String, Value> {
+    let mut metadata = options.commit_metadata.clone();
+    metadata.retain(|key, _| !RESERVED_KEYS.contains(&key.as_str()));
+    metadata.insert(
+        "clientVersion".into(),
+        Value::from(format!("delta-rs.{}", deltalake::crate_version())),
+    );
+    metadata.insert("oxbowVersion".into(), Value::from(OXBOW_VERSION));
+    metadata.insert("oxbowFileCount".into(), Value::from(file_count));
+    metadata
+}
+
+/**
+ * Return the commitInfo action for a commit of `file_count` files by the operation, made against
+ * `read_version` of the table.
+ *
+ * deltalake only writes its own commitInfo when none is among the actions, and the CreateBuilder
+ * ignores the metadata it is given, so oxbow provides a complete one with every commit.
+ */
+pub fn commit_info(
+    operation: &DeltaOperation,
+    read_version: Option<i64>,
+    options: &ConversionOptions,
+    file_count: usize,
+) -> Action {
+    let mut commit_info = operation.get_commit_info();
+    commit_info.timestamp = Some(Utc::now().timestamp_millis());
+    commit_info.read_version = read_version;
+    commit_info.is_blind_append = Some(matches!(
+        operation,
+        DeltaOperation::Create { .. }
+            | DeltaOperation::Write {
+                mode: SaveMode::Append,
+                ..
+            }
+    ));
+    commit_info.engine_info = Some(ENGINE_INFO.into());
+    commit_info.info = commit_metadata(options, file_count);
+    Action::commitInfo(commit_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_with_custom_keys() {
+        let mut options = ConversionOptions::default();
+        options
+            .commit_metadata
+            .insert("sourceBucket".into(), Value::from("my-bucket"));
+        options
+            .commit_metadata
+            .insert("engineInfo".into(), Value::from("spoofed"));
+        options
+            .commit_metadata
+            .insert("oxbowFileCount".into(), Value::from(100));
+
+        let metadata = commit_metadata(&options, 3);
+        assert_eq!(
+            Some(&Value::from("my-bucket")),
+            metadata.get("sourceBucket")
+        );
+        assert_eq!(None, metadata.get("engineInfo"));
+        assert_eq!(Some(&Value::from(3)), metadata.get("oxbowFileCount"));
+        assert!(metadata.contains_key("clientVersion"));
+    }
+
+    #[test]
+    fn commit_info_for_an_append() {
+        let operation = DeltaOperation::Write {
+            mode: SaveMode::Append,
+            partition_by: Some(vec!["ds".into()]),
+            predicate: None,
+        };
+        let Action::commitInfo(commit_info) =
+            commit_info(&operation, Some(4), &ConversionOptions::default(), 2)
+        else {
+            panic!("Expected a commitInfo action");
+        };
+
+        assert_eq!(Some("WRITE".to_string()), commit_info.operation);
+        assert_eq!(
+            Some(&Value::from("Append")),
+            commit_info
+                .operation_parameters
+                .as_ref()
+                .and_then(|p| p.get("mode"))
+        );
+        assert_eq!(Some(4), commit_info.read_version);
+        assert_eq!(Some(true), commit_info.is_blind_append);
+        assert_eq!(Some(ENGINE_INFO.to_string()), commit_info.engine_info);
+        assert!(!commit_info.info.contains_key("engineInfo"));
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * The magic module contains the logic for recognizing parquet files, either by their extension or
+ * by sniffing the `PAR1` magic bytes which every parquet file starts and ends with
+ */
+use deltalake::storage::DeltaObjectStore;
+use deltalake::{ObjectMeta, ObjectStore, Path};
+use futures::StreamExt;
+use tracing::log::*;
+
+use std::sync::Arc;
+
+/// The extensions which are considered parquet files when none are configured
+pub const DEFAULT_EXTENSIONS: [&str; 1] = ["parquet"];
+
+/// The magic bytes at the start and end of every parquet file
+const MAGIC: &[u8; 4] = b"PAR1";
+
+/**
+ * Return true if the path has one of the given extensions, or one of the [DEFAULT_EXTENSIONS]
+ * when none are given. Extensions are compared case-insensitively with or without a leading dot.
+ */
+pub fn has_parquet_extension(path: &Path, extensions: &[String]) -> bool {
+    let Some(extension) = path.extension() else {
+        return false;
+    };
+    if extensions.is_empty() {
+        return DEFAULT_EXTENSIONS
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension));
+    }
+    extensions
+        .iter()
+        .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+}
+
+/**
+ * Read the first and last four bytes of the file and return true if both are the parquet magic
+ * bytes. Files which cannot be read are not considered parquet.
+ */
+pub async fn is_parquet(file: &ObjectMeta, store: Arc<DeltaObjectStore>) -> bool {
+    // The smallest possible parquet file has both magic bytes and a footer length
+    if file.size < 12 {
+        return false;
+    }
+
+    let ranges = [0..MAGIC.len(), file.size - MAGIC.len()..file.size];
+    match store.get_ranges(&file.location, &ranges).await {
+        Ok(bytes) => bytes.iter().all(|b| b.as_ref() == MAGIC),
+        Err(err) => {
+            warn!(
+                "Failed to read the magic bytes of {}, skipping it: {err:?}",
+                file.location
+            );
+            false
+        }
+    }
+}
+
+/**
+ * Return only the files which have the parquet magic bytes, sniffing up to `concurrency` files at
+ * the same time
+ */
+pub async fn sniff_parquet_files(
+    files: Vec<ObjectMeta>,
+    store: Arc<DeltaObjectStore>,
+    concurrency: usize,
+) -> Vec<ObjectMeta> {
+    futures::stream::iter(files)
+        .map(|file| {
+            let store = store.clone();
+            async move {
+                let parquet = is_parquet(&file, store).await;
+                debug!("Sniffed {}, parquet: {parquet}", file.location);
+                (file, parquet)
+            }
+        })
+        .buffered(concurrency.max(1))
+        .filter_map(|(file, parquet)| async move { parquet.then_some(file) })
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_extensions() {
+        assert!(has_parquet_extension(&Path::from("a/b.parquet"), &[]));
+        assert!(has_parquet_extension(&Path::from("a/b.PARQUET"), &[]));
+        assert!(!has_parquet_extension(&Path::from("a/b.parq"), &[]));
+        assert!(!has_parquet_extension(&Path::from("a/b"), &[]));
+    }
+
+    #[test]
+    fn configured_extensions() {
+        let extensions = vec![".parq".to_string(), "pq".to_string()];
+        assert!(has_parquet_extension(&Path::from("a/b.parq"), &extensions));
+        assert!(has_parquet_extension(&Path::from("a/b.pq"), &extensions));
+        assert!(!has_parquet_extension(
+            &Path::from("a/b.parquet"),
+            &extensions
+        ));
+    }
+
+    #[tokio::test]
+    async fn sniff_magic_bytes() {
+        let (tempdir, store) = crate::tests::util::create_empty_temp_path();
+        let batch = deltalake::arrow::record_batch::RecordBatch::try_from_iter(vec![(
+            "id",
+            Arc::new(deltalake::arrow::array::Int32Array::from(vec![1, 2]))
+                as deltalake::arrow::array::ArrayRef,
+        )])
+        .unwrap();
+        crate::tests::util::write_parquet(tempdir.path(), "firehose-output", &batch);
+        store
+            .put(
+                &Path::from("notes.txt"),
+                "PAR1 but not really parquet".into(),
+            )
+            .await
+            .unwrap();
+
+        let files = vec![
+            store.head(&Path::from("firehose-output")).await.unwrap(),
+            store.head(&Path::from("notes.txt")).await.unwrap(),
+        ];
+        let sniffed = sniff_parquet_files(files, store, 2).await;
+        assert_eq!(1, sniffed.len());
+        assert_eq!("firehose-output", sniffed[0].location.as_ref());
+    }
+}
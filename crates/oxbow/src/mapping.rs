@@ -0,0 +1,222 @@
+/*
+ * The mapping module enables Delta's column mapping for tables whose parquet files have column
+ * names which Delta cannot otherwise represent, such as the `total (usd)` columns pandas writes
+ */
+use deltalake::protocol::Protocol;
+use deltalake::schema::{SchemaDataType, SchemaField, SchemaTypeArray, SchemaTypeMap};
+use deltalake::{DeltaTable, SchemaTypeStruct};
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+/// The table property which enables column mapping
+pub const MODE_KEY: &str = "delta.columnMapping.mode";
+/// The table property holding the largest column id which has been assigned
+pub const MAX_COLUMN_ID_KEY: &str = "delta.columnMapping.maxColumnId";
+/// The field metadata holding the column id
+pub const ID_KEY: &str = "delta.columnMapping.id";
+/// The field metadata holding the name of the column in the parquet files
+pub const PHYSICAL_NAME_KEY: &str = "delta.columnMapping.physicalName";
+
+/// The characters Delta writers reject in column names unless column mapping is enabled
+const ILLEGAL_CHARACTERS: [char; 10] = [' ', ',', ';', '{', '}', '(', ')', '\n', '\t', '='];
+
+/// The protocol versions which support column mapping in `name` mode
+pub const PROTOCOL: Protocol = Protocol {
+    min_reader_version: 2,
+    min_writer_version: 5,
+};
+
+/**
+ * Return true if the column name can only be used in a table with column mapping
+ */
+pub fn is_illegal_name(name: &str) -> bool {
+    name.contains(ILLEGAL_CHARACTERS)
+}
+
+/**
+ * Return true if any of the fields, including nested ones, has a name which requires column
+ * mapping
+ */
+pub fn requires_column_mapping(fields: &[SchemaField]) -> bool {
+    fields
+        .iter()
+        .any(|f| is_illegal_name(f.get_name()) || requires_for_type(f.get_type()))
+}
+
+fn requires_for_type(data_type: &SchemaDataType) -> bool {
+    match data_type {
+        SchemaDataType::r#struct(inner) => requires_column_mapping(inner.get_fields()),
+        SchemaDataType::array(inner) => requires_for_type(inner.get_element_type()),
+        SchemaDataType::map(inner) => {
+            requires_for_type(inner.get_key_type()) || requires_for_type(inner.get_value_type())
+        }
+        SchemaDataType::primitive(_) => false,
+    }
+}
+
+/**
+ * Return the table's column mapping mode, if it has one other than `none`
+ */
+pub fn mode_of(table: &DeltaTable) -> Option<String> {
+    table
+        .get_metadata()
+        .ok()?
+        .configuration
+        .get(MODE_KEY)
+        .cloned()
+        .flatten()
+        .filter(|mode| mode != "none")
+}
+
+/**
+ * Return the largest column id assigned to any of the fields, or 0 if none have one
+ */
+pub fn max_column_id(fields: &[SchemaField]) -> i64 {
+    fields
+        .iter()
+        .map(|f| {
+            let own = f
+                .get_metadata()
+                .get(ID_KEY)
+                .and_then(Value::as_i64)
+                .unwrap_or(0);
+            own.max(max_id_for_type(f.get_type()))
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn max_id_for_type(data_type: &SchemaDataType) -> i64 {
+    match data_type {
+        SchemaDataType::r#struct(inner) => max_column_id(inner.get_fields()),
+        SchemaDataType::array(inner) => max_id_for_type(inner.get_element_type()),
+        SchemaDataType::map(inner) => {
+            max_id_for_type(inner.get_key_type()).max(max_id_for_type(inner.get_value_type()))
+        }
+        SchemaDataType::primitive(_) => 0,
+    }
+}
+
+/**
+ * Assign a column id to every field, including nested ones, which does not already have one,
+ * starting after `max_id`. Returns the fields and the largest id assigned.
+ *
+ * The physical name of every field is its current name, so that existing parquet files can be
+ * read in place without being rewritten.
+ */
+pub fn with_column_mapping(fields: Vec<SchemaField>, max_id: i64) -> (Vec<SchemaField>, i64) {
+    let mut next = max_id;
+    let fields = fields
+        .into_iter()
+        .map(|f| map_field(f, &mut next))
+        .collect();
+    (fields, next)
+}
+
+fn map_field(field: SchemaField, next: &mut i64) -> SchemaField {
+    let mut metadata = field.get_metadata().clone();
+    if !metadata.contains_key(ID_KEY) {
+        *next += 1;
+        metadata.insert(ID_KEY.into(), Value::from(*next));
+    }
+    metadata
+        .entry(PHYSICAL_NAME_KEY.into())
+        .or_insert_with(|| Value::from(field.get_name()));
+
+    SchemaField::new(
+        field.get_name().to_string(),
+        map_type(field.get_type().clone(), next),
+        field.is_nullable(),
+        metadata,
+    )
+}
+
+fn map_type(data_type: SchemaDataType, next: &mut i64) -> SchemaDataType {
+    match data_type {
+        SchemaDataType::r#struct(inner) => SchemaDataType::r#struct(SchemaTypeStruct::new(
+            inner
+                .get_fields()
+                .iter()
+                .cloned()
+                .map(|f| map_field(f, next))
+                .collect(),
+        )),
+        SchemaDataType::array(inner) => SchemaDataType::array(SchemaTypeArray::new(
+            Box::new(map_type(inner.get_element_type().clone(), next)),
+            inner.contains_null(),
+        )),
+        SchemaDataType::map(inner) => SchemaDataType::map(SchemaTypeMap::new(
+            Box::new(map_type(inner.get_key_type().clone(), next)),
+            Box::new(map_type(inner.get_value_type().clone(), next)),
+            inner.get_value_contains_null(),
+        )),
+        primitive => primitive,
+    }
+}
+
+/**
+ * Return the table properties which enable column mapping in `name` mode for the fields
+ */
+pub fn configuration(fields: &[SchemaField]) -> HashMap<String, Option<String>> {
+    HashMap::from([
+        (MODE_KEY.to_string(), Some("name".to_string())),
+        (
+            MAX_COLUMN_ID_KEY.to_string(),
+            Some(max_column_id(fields).to_string()),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, data_type: SchemaDataType) -> SchemaField {
+        SchemaField::new(name.into(), data_type, true, HashMap::new())
+    }
+
+    fn string() -> SchemaDataType {
+        SchemaDataType::primitive("string".into())
+    }
+
+    #[test]
+    fn illegal_names() {
+        assert!(is_illegal_name("total (usd)"));
+        assert!(is_illegal_name("a,b"));
+        assert!(is_illegal_name("x=1"));
+        assert!(!is_illegal_name("total_usd"));
+        assert!(!is_illegal_name("Total-USD.2"));
+    }
+
+    #[test]
+    fn nested_names_require_mapping() {
+        let nested = SchemaDataType::r#struct(SchemaTypeStruct::new(vec![field("a b", string())]));
+        assert!(requires_column_mapping(&[field("outer", nested)]));
+        assert!(!requires_column_mapping(&[field("outer", string())]));
+    }
+
+    #[test]
+    fn assign_column_ids() {
+        let nested = SchemaDataType::r#struct(SchemaTypeStruct::new(vec![field("a b", string())]));
+        let (fields, max_id) =
+            with_column_mapping(vec![field("id", string()), field("outer", nested)], 0);
+        assert_eq!(3, max_id);
+        assert_eq!(3, max_column_id(&fields));
+        assert_eq!(Some(&Value::from(1)), fields[0].get_metadata().get(ID_KEY));
+        assert_eq!(
+            Some(&Value::from("id")),
+            fields[0].get_metadata().get(PHYSICAL_NAME_KEY)
+        );
+
+        // Fields which already have an id keep it
+        let (fields, max_id) =
+            with_column_mapping(vec![fields[0].clone(), field("new col", string())], max_id);
+        assert_eq!(4, max_id);
+        assert_eq!(Some(&Value::from(1)), fields[0].get_metadata().get(ID_KEY));
+        assert_eq!(
+            Some(&Some("4".to_string())),
+            configuration(&fields).get(MAX_COLUMN_ID_KEY)
+        );
+    }
+}
@@ -0,0 +1,666 @@
+/*
+ * The partitions module contains the logic for extracting partition values from file paths, either
+ * hive-style `key=value` segments or a configured path template, and inferring the types of the
+ * partition columns from the values observed
+ */
+use deltalake::{ObjectMeta, SchemaDataType};
+use tracing::log::*;
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::error::{OxbowError, OxbowResult};
+
+/// The directory name Hive and Spark write for a null partition value
+pub const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// The Delta primitive types which partition columns can be declared as
+const PARTITION_TYPES: [&str; 11] = [
+    "string",
+    "long",
+    "integer",
+    "short",
+    "byte",
+    "float",
+    "double",
+    "boolean",
+    "binary",
+    "date",
+    "timestamp",
+];
+
+/// How partition values are encoded in the paths of files, relative to the table location
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PartitionLayout {
+    /// Directories named like `key=value`
+    #[default]
+    Hive,
+    /// Directories whose names are the values of the columns in a [PathTemplate]
+    Template(PathTemplate),
+}
+
+impl FromStr for PartitionLayout {
+    type Err = OxbowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "hive" => Ok(Self::Hive),
+            _ => Ok(Self::Template(s.parse()?)),
+        }
+    }
+}
+
+impl PartitionLayout {
+    /**
+     * Return the partition values encoded in the directories of the path, in the form used by Add
+     * and Remove actions. The file name itself is never considered a partition.
+     *
+     * Hive-style directories are unescaped following Hive's rules, and [HIVE_DEFAULT_PARTITION]
+     * is a null value. Paths which do not [PartitionLayout::matches] the layout have no values, so
+     * they should be skipped rather than committed.
+     */
+    pub fn partition_values_from(&self, path: &str) -> HashMap<String, Option<String>> {
+        match self {
+            Self::Hive => directories_of(path)
+                .filter_map(|directory| directory.split_once('='))
+                .filter(|(key, _)| !key.is_empty())
+                .map(|(key, value)| {
+                    let value = match value {
+                        HIVE_DEFAULT_PARTITION => None,
+                        value => Some(unescape_path_name(value)),
+                    };
+                    (unescape_path_name(key), value)
+                })
+                .collect(),
+            Self::Template(template) => template.partition_values_from(path).unwrap_or_default(),
+        }
+    }
+
+    /**
+     * Return true if the partition values can be read from the path, which is always the case for
+     * hive-style directories
+     */
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Hive => true,
+            Self::Template(template) => template.partition_values_from(path).is_some(),
+        }
+    }
+
+    /**
+     * Return the partition columns of the given files. A template always has all of its columns,
+     * while hive-style columns are only those which are present in at least one path.
+     */
+    pub fn columns_from(&self, files: &[ObjectMeta]) -> Vec<String> {
+        match self {
+            Self::Hive => {
+                // The HashSet is only to prevent collecting redundant partitions
+                let mut results = HashSet::new();
+                for file in files.iter() {
+                    results.extend(
+                        self.partition_values_from(file.location.as_ref())
+                            .into_keys(),
+                    );
+                }
+                results.into_iter().collect()
+            }
+            Self::Template(template) => template.columns(),
+        }
+    }
+
+    /**
+     * Return the number of directories between the table location and its files, if it is fixed
+     * by the layout
+     */
+    pub fn depth(&self) -> Option<usize> {
+        match self {
+            Self::Hive => None,
+            Self::Template(template) => Some(template.segments.len()),
+        }
+    }
+}
+
+/// A segment of a [PathTemplate]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A directory whose name is the value of the column, written as `{column}`
+    Column(String),
+    /// A directory which must have exactly this name
+    Literal(String),
+}
+
+/**
+ * A template like `{year}/{month}/{day}/{hour}` which maps the leading directories of a file's
+ * path, relative to the table location, to partition values. This is how Firehose and many other
+ * exporters lay out their output.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+impl FromStr for PathTemplate {
+    type Err = OxbowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            OxbowError::InvalidConfiguration(format!("Invalid partition template `{s}`: {reason}"))
+        };
+
+        let mut segments = vec![];
+        let mut seen = HashSet::new();
+        for part in s.trim_matches('/').split('/') {
+            if part.is_empty() {
+                return Err(invalid("it has an empty directory"));
+            }
+            match part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                Some(column) => {
+                    if column.is_empty() || column.contains(['{', '}']) {
+                        return Err(invalid("columns must be written like `{name}`"));
+                    }
+                    if !seen.insert(column) {
+                        return Err(invalid(&format!("the column `{column}` is repeated")));
+                    }
+                    segments.push(Segment::Column(column.to_string()));
+                }
+                None if part.contains(['{', '}']) => {
+                    return Err(invalid("a column must be a whole directory"));
+                }
+                None => segments.push(Segment::Literal(part.to_string())),
+            }
+        }
+
+        if seen.is_empty() {
+            return Err(invalid("it has no columns"));
+        }
+        Ok(Self { segments })
+    }
+}
+
+impl PathTemplate {
+    /**
+     * Return the names of the template's columns in order
+     */
+    pub fn columns(&self) -> Vec<String> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Column(column) => Some(column.clone()),
+                Segment::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    /**
+     * Return the partition values of the path, or `None` if the directories of the path do not
+     * match the template
+     */
+    pub fn partition_values_from(&self, path: &str) -> Option<HashMap<String, Option<String>>> {
+        let directories: Vec<&str> = directories_of(path).collect();
+        if directories.len() < self.segments.len() {
+            debug!("The path {path} is too short for the partition template");
+            return None;
+        }
+
+        let mut values = HashMap::new();
+        for (segment, directory) in self.segments.iter().zip(directories) {
+            match segment {
+                Segment::Column(key) => {
+                    values.insert(key.clone(), Some(directory.to_string()));
+                }
+                Segment::Literal(name) if name == directory => {}
+                Segment::Literal(_) => {
+                    debug!("The path {path} does not match the partition template");
+                    return None;
+                }
+            }
+        }
+        Some(values)
+    }
+}
+
+/**
+ * Return the directory segments of the path, leaving off the file name
+ */
+fn directories_of(path: &str) -> impl Iterator<Item = &str> {
+    let directories = path.rsplit_once('/').map(|(dirs, _)| dirs).unwrap_or("");
+    directories.split('/').filter(|d| !d.is_empty())
+}
+
+/**
+ * Return true if Hive escapes the character when writing it into a partition directory name,
+ * following `FileUtils.escapePathName`
+ */
+#[cfg(test)]
+fn needs_escaping(c: char) -> bool {
+    matches!(
+        c,
+        '\u{01}'
+            ..='\u{1F}'
+                | '"'
+                | '#'
+                | '%'
+                | '\''
+                | '*'
+                | '/'
+                | ':'
+                | '='
+                | '?'
+                | '\\'
+                | '\u{7F}'
+                | '{'
+                | '['
+                | ']'
+                | '^'
+    )
+}
+
+/**
+ * Escape a partition column name or value the way Hive and Spark do when writing it into a
+ * directory name, e.g. `a:b` becomes `a%3Ab`. A null value should be written as
+ * [HIVE_DEFAULT_PARTITION] instead.
+ */
+#[cfg(test)]
+fn escape_path_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if needs_escaping(c) {
+            escaped.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/**
+ * Unescape a partition column name or value from a directory name, the reverse of
+ * Hive's `FileUtils.escapePathName`. A `%` which is not followed by two hex digits is kept as-is, like Hive does.
+ */
+pub fn unescape_path_name(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| name.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(byte) => {
+                unescaped.push(byte);
+                i += 3;
+            }
+            None => {
+                unescaped.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(unescaped).unwrap_or_else(|_| name.to_string())
+}
+
+/**
+ * Return the Delta type of each of the given partition columns, which is the type provided in
+ * `overrides` or otherwise a string.
+ *
+ * With `infer` the types of the other columns are inferred from the values found in the paths of
+ * the files, as read with the [PartitionLayout]. A column is only given a non-string type if every
+ * observed non-null value parses as that type.
+ */
+pub fn partition_types_from(
+    files: &[ObjectMeta],
+    partitions: &[String],
+    overrides: &HashMap<String, String>,
+    layout: &PartitionLayout,
+    infer: bool,
+) -> OxbowResult<HashMap<String, SchemaDataType>> {
+    let mut values: HashMap<&str, Vec<String>> = HashMap::new();
+
+    for file in files.iter() {
+        for (key, value) in layout.partition_values_from(file.location.as_ref()) {
+            // Null partitions say nothing about the type of the column
+            let Some(value) = value else {
+                continue;
+            };
+            if let Some(column) = partitions.iter().find(|p| **p == key) {
+                values.entry(column.as_str()).or_default().push(value);
+            }
+        }
+    }
+
+    let mut types = HashMap::new();
+    for partition in partitions.iter() {
+        let type_name = match overrides.get(partition) {
+            Some(type_name) => {
+                if !is_valid_type(type_name) {
+                    return Err(OxbowError::InvalidConfiguration(format!(
+                        "The type `{type_name}` cannot be used for the partition column `{partition}`"
+                    )));
+                }
+                type_name.clone()
+            }
+            None if infer => {
+                let observed = values.get(partition.as_str()).cloned().unwrap_or_default();
+                infer_type(&observed).to_string()
+            }
+            None => "string".to_string(),
+        };
+        debug!("Using the type {type_name} for the partition column {partition}");
+        types.insert(partition.clone(), SchemaDataType::primitive(type_name));
+    }
+    Ok(types)
+}
+
+/**
+ * Return true if the type name is a Delta primitive type which partition columns support
+ */
+fn is_valid_type(type_name: &str) -> bool {
+    PARTITION_TYPES.contains(&type_name)
+        || (type_name.starts_with("decimal(") && type_name.ends_with(')'))
+}
+
+/**
+ * Return the narrowest Delta type name which all the values can be parsed as.
+ *
+ * Zero-padded numbers such as `01` are only read as numbers when every value has the same width,
+ * as with `month=01` through `month=12`, since the padding can then be recreated. Otherwise they
+ * are left as strings, and the type can still be given explicitly with `--partition-type`.
+ */
+fn infer_type(values: &[String]) -> &'static str {
+    if values.is_empty() {
+        return "string";
+    }
+
+    let padded = values.iter().any(|v| {
+        let digits = v.trim_start_matches('-');
+        digits.len() > 1 && digits.starts_with('0')
+    });
+    let same_width = values.iter().all(|v| v.len() == values[0].len());
+    let candidates: &[&'static str] = match padded && !same_width {
+        true => &["boolean", "date", "timestamp"],
+        false => &["boolean", "integer", "long", "date", "timestamp"],
+    };
+    candidates
+        .iter()
+        .find(|type_name| values.iter().all(|v| is_value_of(v, type_name)))
+        .copied()
+        .unwrap_or("string")
+}
+
+/**
+ * Return true if the partition value can be read as a value of the Delta primitive type
+ */
+pub fn is_value_of(value: &str, type_name: &str) -> bool {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    match type_name {
+        "boolean" => value.parse::<bool>().is_ok(),
+        "byte" => value.parse::<i8>().is_ok(),
+        "short" => value.parse::<i16>().is_ok(),
+        "integer" => value.parse::<i32>().is_ok(),
+        "long" => value.parse::<i64>().is_ok(),
+        "float" | "double" => value.parse::<f64>().is_ok(),
+        "date" => NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        "timestamp" => NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").is_ok(),
+        decimal if decimal.starts_with("decimal(") => value.parse::<f64>().is_ok(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::prelude::Utc;
+    use deltalake::Path;
+
+    fn files_at(paths: &[&str]) -> Vec<ObjectMeta> {
+        paths
+            .iter()
+            .map(|p| ObjectMeta {
+                location: Path::from(*p),
+                last_modified: Utc::now(),
+                size: 1024,
+                e_tag: None,
+            })
+            .collect()
+    }
+
+    fn values(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn infer_type_of_values() {
+        assert_eq!("string", infer_type(&[]));
+        assert_eq!("boolean", infer_type(&values(&["true", "false"])));
+        assert_eq!("integer", infer_type(&values(&["2023", "1", "-12"])));
+        assert_eq!("string", infer_type(&values(&["2023", "01"])));
+        assert_eq!("string", infer_type(&values(&["01", "10", "7"])));
+        assert_eq!("integer", infer_type(&values(&["01", "10", "12"])));
+        assert_eq!("integer", infer_type(&values(&["0012"])));
+        assert_eq!("long", infer_type(&values(&["1", "20180512000000"])));
+        assert_eq!("date", infer_type(&values(&["2023-12-12", "2023-01-01"])));
+        assert_eq!(
+            "timestamp",
+            infer_type(&values(&["2023-12-12 01:02:03", "2023-12-12 01:02:03.456"]))
+        );
+        assert_eq!("string", infer_type(&values(&["2023", "foo"])));
+    }
+
+    #[test]
+    fn partition_types_from_paths() {
+        let files = files_at(&[
+            "year=2023/month=10/ds=2023-10-01/a.parquet",
+            "year=2023/month=11/ds=2023-11-01/b.parquet",
+        ]);
+        let partitions = vec!["year".to_string(), "month".to_string(), "ds".to_string()];
+
+        let types = partition_types_from(
+            &files,
+            &partitions,
+            &HashMap::new(),
+            &PartitionLayout::Hive,
+            true,
+        )
+        .expect("Failed to infer types");
+        assert_eq!(
+            Some(&SchemaDataType::primitive("integer".into())),
+            types.get("year")
+        );
+        assert_eq!(
+            Some(&SchemaDataType::primitive("integer".into())),
+            types.get("month")
+        );
+        assert_eq!(
+            Some(&SchemaDataType::primitive("date".into())),
+            types.get("ds")
+        );
+    }
+
+    #[test]
+    fn partition_types_are_strings_by_default() {
+        let files = files_at(&["year=2023/month=01/a.parquet"]);
+        let partitions = vec!["year".to_string(), "month".to_string()];
+        let types = partition_types_from(
+            &files,
+            &partitions,
+            &HashMap::new(),
+            &PartitionLayout::Hive,
+            false,
+        )
+        .expect("Failed to infer types");
+        for partition in partitions.iter() {
+            assert_eq!(
+                Some(&SchemaDataType::primitive("string".into())),
+                types.get(partition)
+            );
+        }
+    }
+
+    #[test]
+    fn values_of_types() {
+        assert!(is_value_of("latest", "string"));
+        assert!(is_value_of("20180520", "integer"));
+        assert!(!is_value_of("latest", "integer"));
+        assert!(!is_value_of("3000000000", "integer"));
+        assert!(is_value_of("3000000000", "long"));
+        assert!(is_value_of("2023-10-17", "date"));
+        assert!(!is_value_of("2023-10-17", "timestamp"));
+        assert!(is_value_of("1.5", "decimal(10,2)"));
+    }
+
+    #[test]
+    fn partition_types_with_overrides() {
+        let files = files_at(&["year=2023/a.parquet"]);
+        let partitions = vec!["year".to_string()];
+        let overrides = HashMap::from([("year".to_string(), "string".to_string())]);
+
+        let types = partition_types_from(
+            &files,
+            &partitions,
+            &overrides,
+            &PartitionLayout::Hive,
+            false,
+        )
+        .expect("Failed to infer types");
+        assert_eq!(
+            Some(&SchemaDataType::primitive("string".into())),
+            types.get("year")
+        );
+    }
+
+    #[test]
+    fn partition_types_with_invalid_override() {
+        let files = files_at(&["year=2023/a.parquet"]);
+        let partitions = vec!["year".to_string()];
+        let overrides = HashMap::from([("year".to_string(), "struct".to_string())]);
+
+        let result = partition_types_from(
+            &files,
+            &partitions,
+            &overrides,
+            &PartitionLayout::Hive,
+            false,
+        );
+        assert!(result.is_err(), "Should not allow a non-primitive type");
+    }
+
+    #[test]
+    fn hive_partitions_ignore_the_file_name() {
+        let values = PartitionLayout::Hive
+            .partition_values_from("ds=1/testing_oxbow-partitioned2_ds=2.parquet");
+        assert_eq!(
+            HashMap::from([("ds".to_string(), Some("1".to_string()))]),
+            values
+        );
+        assert!(PartitionLayout::Hive
+            .partition_values_from("a=b.parquet")
+            .is_empty());
+    }
+
+    #[test]
+    fn hive_escaped_partition_values() {
+        let values = PartitionLayout::Hive.partition_values_from(
+            "c2=+ %3D%250/ts=2023-10-17 13%3A00%3A00/p=a%2Fb/n=__HIVE_DEFAULT_PARTITION__/a.parquet",
+        );
+        assert_eq!(Some(&Some("+ =%0".to_string())), values.get("c2"));
+        assert_eq!(
+            Some(&Some("2023-10-17 13:00:00".to_string())),
+            values.get("ts")
+        );
+        assert_eq!(Some(&Some("a/b".to_string())), values.get("p"));
+        assert_eq!(Some(&None), values.get("n"));
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        assert_eq!("+ %3D%250", escape_path_name("+ =%0"));
+        assert_eq!("13%3A00%3A00", escape_path_name("13:00:00"));
+        assert_eq!("a%2Fb%5C%7B%5B", escape_path_name("a/b\\{["));
+        for value in ["+ =%0", "a/b:c", "é#?", "plain"] {
+            assert_eq!(value, unescape_path_name(&escape_path_name(value)));
+        }
+        // Invalid escapes are kept as they are
+        assert_eq!("100%", unescape_path_name("100%"));
+        assert_eq!("%zz", unescape_path_name("%zz"));
+    }
+
+    #[test]
+    fn partition_types_ignore_nulls() {
+        let files = files_at(&[
+            "year=2023/a.parquet",
+            "year=__HIVE_DEFAULT_PARTITION__/b.parquet",
+        ]);
+        let partitions = vec!["year".to_string()];
+        let types = partition_types_from(
+            &files,
+            &partitions,
+            &HashMap::new(),
+            &PartitionLayout::Hive,
+            true,
+        )
+        .expect("Failed to infer types");
+        assert_eq!(
+            Some(&SchemaDataType::primitive("integer".into())),
+            types.get("year")
+        );
+    }
+
+    #[test]
+    fn parse_templates() {
+        assert_eq!(PartitionLayout::Hive, "hive".parse().unwrap());
+        let template: PathTemplate = "/{year}/{month}/{day}/{hour}/".parse().unwrap();
+        assert_eq!(vec!["year", "month", "day", "hour"], template.columns());
+        assert!("year/month".parse::<PathTemplate>().is_err());
+        assert!("{year}/{year}".parse::<PathTemplate>().is_err());
+        assert!("{year}//{month}".parse::<PathTemplate>().is_err());
+        assert!("dt-{day}".parse::<PathTemplate>().is_err());
+        assert!("{}".parse::<PathTemplate>().is_err());
+    }
+
+    #[test]
+    fn template_partitions_from_paths() {
+        let layout: PartitionLayout = "{year}/{month}/{day}/{hour}".parse().unwrap();
+        let values = layout.partition_values_from("2023/10/17/13/file.parquet");
+        assert_eq!(4, values.len());
+        assert_eq!(Some(&Some("2023".to_string())), values.get("year"));
+        assert_eq!(Some(&Some("13".to_string())), values.get("hour"));
+
+        assert!(layout.matches("2023/10/17/13/file.parquet"));
+        assert!(!layout.matches("2023/10/17/file.parquet"));
+        assert!(!layout.matches("file.parquet"));
+        assert!(layout
+            .partition_values_from("2023/10/17/file.parquet")
+            .is_empty());
+        assert_eq!(Some(4), layout.depth());
+
+        let files = files_at(&["2023/10/17/13/a.parquet", "2023/10/18/21/b.parquet"]);
+        let types = partition_types_from(
+            &files,
+            &layout.columns_from(&files),
+            &HashMap::new(),
+            &layout,
+            true,
+        )
+        .expect("Failed to infer types");
+        assert_eq!(
+            Some(&SchemaDataType::primitive("integer".into())),
+            types.get("hour")
+        );
+    }
+
+    #[test]
+    fn template_with_literal_directories() {
+        let layout: PartitionLayout = "events/{ds}".parse().unwrap();
+        assert_eq!(
+            1,
+            layout
+                .partition_values_from("events/2023-10-17/a.parquet")
+                .len()
+        );
+        assert!(!layout.matches("other/2023-10-17/a.parquet"));
+        assert!(PartitionLayout::Hive.matches("other/2023-10-17/a.parquet"));
+    }
+}
@@ -0,0 +1,157 @@
+/*
+ * The plan module describes what oxbow would do to create a Delta table from a set of files,
+ * without actually doing it
+ */
+use deltalake::schema::Schema;
+use deltalake::{ObjectMeta, SchemaDataType, SchemaField};
+use serde::Serialize;
+use tracing::log::*;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{OxbowError, OxbowResult};
+use crate::footers::FooterCache;
+use crate::types::TypeCoercion;
+use crate::validate::Quarantine;
+use crate::ConversionOptions;
+
+/// A description of the Delta table oxbow would create for a location
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversionPlan {
+    /// The version of the Delta table already at the location, in which case nothing will be done
+    pub existing_version: Option<i64>,
+    /// The parquet files which would be added to the table
+    pub files: Vec<PlannedFile>,
+    /// The schema the table would be created with, including partition columns
+    pub schema: Option<Schema>,
+    /// The columns the table would be partitioned by
+    pub partition_columns: Vec<String>,
+    /// Columns whose types would be changed to be represented in the Delta schema
+    pub coercions: Vec<TypeCoercion>,
+    /// Whether the table would use column mapping because some column names are not otherwise
+    /// allowed by Delta
+    pub column_mapping: bool,
+    /// Parquet files which were discovered but would not be added to the table
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// A parquet file which would be added to the table
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFile {
+    pub path: String,
+    pub size: usize,
+}
+
+/// A parquet file which would not be added to the table, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/**
+ * Plan the creation of a Delta table from the given files, reading their footers but without
+ * writing anything to the store
+ */
+pub async fn plan_for(
+    files: &[ObjectMeta],
+    footers: &FooterCache,
+    options: &ConversionOptions,
+) -> OxbowResult<ConversionPlan> {
+    // Invalid table properties would fail the conversion, so they should fail the plan too
+    crate::properties::table_configuration(&options.table_properties)?;
+
+    let mut skipped: Vec<SkippedFile> = vec![];
+    let validated;
+    let files = match options.quarantine {
+        Quarantine::Disabled => files,
+        _ => {
+            validated = crate::validate::validate_files(files, footers, None).await;
+            skipped.extend(validated.invalid.iter().map(|invalid| SkippedFile {
+                path: invalid.file.location.to_string(),
+                reason: invalid.reason.clone(),
+            }));
+            validated.valid.as_slice()
+        }
+    };
+    if files.is_empty() {
+        return Err(OxbowError::IncompatibleFiles {
+            files: skipped.into_iter().map(|s| (s.path, s.reason)).collect(),
+        });
+    }
+
+    let partitions = options.partition_layout.columns_from(files);
+
+    info!(
+        "Inferring the table schema from {} parquet files",
+        options.schema_sample.unwrap_or(files.len())
+    );
+    let unified = crate::schema::unify_schemas(files, footers, options.schema_sample).await?;
+    debug!(
+        "Unified schema from the Parquet files: {:?}",
+        unified.schema
+    );
+
+    let (schema, coercions) = crate::delta_schema_from(&unified.schema)?;
+
+    let mut columns = schema.get_fields().clone();
+    let partition_types = crate::partitions::partition_types_from(
+        files,
+        &partitions,
+        &options.partition_types,
+        &options.partition_layout,
+        options.infer_partition_types,
+    )?;
+
+    for partition in &partitions {
+        // Only add the partition if it does not already exist in the schema
+        if schema.get_field_with_name(partition).is_err() {
+            let field = SchemaField::new(
+                partition.into(),
+                partition_types
+                    .get(partition)
+                    .cloned()
+                    .unwrap_or_else(|| SchemaDataType::primitive("string".into())),
+                true,
+                HashMap::new(),
+            );
+            columns.push(field);
+        }
+    }
+
+    let column_mapping = crate::mapping::requires_column_mapping(&columns);
+    if column_mapping {
+        info!("Some column names are not allowed by Delta, the table will use column mapping");
+        columns = crate::mapping::with_column_mapping(columns, 0).0;
+    }
+
+    skipped.extend(
+        unified
+            .incompatible
+            .into_iter()
+            .map(|(path, reason)| SkippedFile {
+                path: path.to_string(),
+                reason,
+            }),
+    );
+
+    let skipped_paths: HashSet<&str> = skipped.iter().map(|s| s.path.as_str()).collect();
+    let files = files
+        .iter()
+        .filter(|f| !skipped_paths.contains(f.location.as_ref()))
+        .map(|f| PlannedFile {
+            path: f.location.to_string(),
+            size: f.size,
+        })
+        .collect();
+
+    Ok(ConversionPlan {
+        existing_version: None,
+        files,
+        schema: Some(Schema::new(columns)),
+        partition_columns: partitions,
+        coercions,
+        column_mapping,
+        skipped,
+    })
+}
@@ -0,0 +1,213 @@
+/*
+ * The properties module validates the table properties oxbow is asked to create tables with, and
+ * parses the values of the ones oxbow itself acts upon
+ */
+use deltalake::{DeltaConfigKey, DeltaTable};
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::{OxbowError, OxbowResult};
+
+/**
+ * Validate the table properties and return them as the configuration of a new table.
+ *
+ * Keys starting with `delta.` must be properties known to Delta, and the values of those oxbow
+ * acts upon must parse. Properties which oxbow manages itself, such as the protocol versions and
+ * column mapping, cannot be set. Any other keys are passed through as they are.
+ */
+pub fn table_configuration(
+    properties: &HashMap<String, String>,
+) -> OxbowResult<HashMap<String, Option<String>>> {
+    let invalid = |message: String| Err(OxbowError::InvalidConfiguration(message));
+
+    for (key, value) in properties.iter() {
+        if !key.starts_with("delta.") {
+            continue;
+        }
+        let Ok(config_key) = DeltaConfigKey::from_str(key) else {
+            return invalid(format!("`{key}` is not a Delta table property"));
+        };
+        match config_key {
+            DeltaConfigKey::MinReaderVersion
+            | DeltaConfigKey::MinWriterVersion
+            | DeltaConfigKey::ColumnMappingMode => {
+                return invalid(format!("`{key}` is managed by oxbow and cannot be set"));
+            }
+            DeltaConfigKey::AppendOnly | DeltaConfigKey::EnableExpiredLogCleanup
+                if value.parse::<bool>().is_err() =>
+            {
+                return invalid(format!("`{key}` must be true or false, not `{value}`"));
+            }
+            DeltaConfigKey::CheckpointInterval if value.parse::<u64>().unwrap_or(0) == 0 => {
+                return invalid(format!("`{key}` must be a positive number, not `{value}`"));
+            }
+            DeltaConfigKey::LogRetentionDuration
+            | DeltaConfigKey::DeletedFileRetentionDuration
+            | DeltaConfigKey::SetTransactionRetentionDuration => {
+                parse_interval(value)?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(properties
+        .iter()
+        .map(|(key, value)| (key.clone(), Some(value.clone())))
+        .collect())
+}
+
+/**
+ * Return true if the table has `delta.appendOnly` set, in which case nothing may be removed from it
+ */
+pub fn is_append_only(table: &DeltaTable) -> bool {
+    table
+        .get_metadata()
+        .ok()
+        .and_then(|metadata| {
+            metadata
+                .configuration
+                .get(DeltaConfigKey::AppendOnly.as_ref())
+                .cloned()
+                .flatten()
+        })
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(false)
+}
+
+/**
+ * Parse `KEY=VALUE` pairs into a map, as given on the command line or in the environment
+ */
+pub fn parse_pairs(pairs: &[String]) -> OxbowResult<HashMap<String, String>> {
+    let mut parsed = HashMap::new();
+    for pair in pairs.iter() {
+        match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                parsed.insert(key.trim().to_string(), value.trim().to_string());
+            }
+            _ => {
+                return Err(OxbowError::InvalidConfiguration(format!(
+                    "Expected KEY=VALUE, not `{pair}`"
+                )))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/**
+ * Parse a Delta interval property like `interval 30 days` into a [Duration]
+ */
+pub fn parse_interval(value: &str) -> OxbowResult<Duration> {
+    let invalid = || {
+        OxbowError::InvalidConfiguration(format!(
+            "`{value}` is not an interval like `interval 30 days`"
+        ))
+    };
+
+    let mut parts = value.split_whitespace();
+    if !parts
+        .next()
+        .map(|p| p.eq_ignore_ascii_case("interval"))
+        .unwrap_or(false)
+    {
+        return Err(invalid());
+    }
+    let count: u64 = parts
+        .next()
+        .and_then(|c| c.parse().ok())
+        .ok_or_else(invalid)?;
+    let unit = parts.next().ok_or_else(invalid)?.to_lowercase();
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    let seconds = match unit.trim_end_matches('s') {
+        "nanosecond" => return Ok(Duration::from_nanos(count)),
+        "microsecond" => return Ok(Duration::from_micros(count)),
+        "millisecond" => return Ok(Duration::from_millis(count)),
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(
+        count.checked_mul(seconds).ok_or_else(invalid)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn valid_properties() {
+        let configuration = table_configuration(&properties(&[
+            ("delta.appendOnly", "true"),
+            ("delta.checkpointInterval", "20"),
+            ("delta.logRetentionDuration", "interval 7 days"),
+            ("owner", "data-eng"),
+        ]))
+        .expect("Failed to validate properties");
+        assert_eq!(4, configuration.len());
+        assert_eq!(
+            Some(&Some("true".to_string())),
+            configuration.get("delta.appendOnly")
+        );
+    }
+
+    #[test]
+    fn invalid_properties() {
+        for (key, value) in [
+            ("delta.appendonly", "true"),
+            ("delta.appendOnly", "yes"),
+            ("delta.checkpointInterval", "0"),
+            ("delta.logRetentionDuration", "7 days"),
+            ("delta.minWriterVersion", "7"),
+            ("delta.columnMapping.mode", "id"),
+        ] {
+            assert!(
+                table_configuration(&properties(&[(key, value)])).is_err(),
+                "{key}={value} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_key_value_pairs() {
+        let pairs = parse_pairs(&["delta.appendOnly=true".into(), "a = b=c".into()])
+            .expect("Failed to parse pairs");
+        assert_eq!(Some(&"true".to_string()), pairs.get("delta.appendOnly"));
+        assert_eq!(Some(&"b=c".to_string()), pairs.get("a"));
+        assert!(parse_pairs(&["novalue".into()]).is_err());
+    }
+
+    #[test]
+    fn parse_intervals() {
+        assert_eq!(
+            Duration::from_secs(30 * 24 * 60 * 60),
+            parse_interval("interval 30 days").unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(60 * 60),
+            parse_interval("INTERVAL 1 hour").unwrap()
+        );
+        assert_eq!(
+            Duration::from_millis(5),
+            parse_interval("interval 5 milliseconds").unwrap()
+        );
+        assert!(parse_interval("interval 1 fortnight").is_err());
+        assert!(parse_interval("30 days").is_err());
+        assert!(parse_interval("interval 99999999999999999 weeks").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+/*
+ * The rewrite module contains the logic for physically rewriting parquet files whose contents
+ * cannot be represented faithfully in a Delta table, such as timestamps which are not stored with
+ * microsecond precision
+ */
+use deltalake::arrow::array::{Array, ArrayRef, LargeListArray, ListArray, MapArray, StructArray};
+use deltalake::arrow::compute::cast;
+use deltalake::arrow::datatypes::{DataType, Field, Fields, Schema as ArrowSchema, TimeUnit};
+use deltalake::arrow::record_batch::RecordBatch;
+use deltalake::parquet::arrow::async_reader::{
+    ParquetObjectReader, ParquetRecordBatchStreamBuilder,
+};
+use deltalake::parquet::arrow::AsyncArrowWriter;
+use deltalake::storage::DeltaObjectStore;
+use deltalake::{ObjectMeta, ObjectStore, Path};
+use futures::StreamExt;
+use tracing::log::*;
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::{OxbowError, OxbowResult};
+use crate::footers::FooterCache;
+
+/// How oxbow should handle parquet files with timestamps Delta cannot represent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampRewrite {
+    /// Commit the files as they are, pretending the timestamps are microseconds
+    #[default]
+    Disabled,
+    /// Rewrite the files with microsecond timestamps and leave the originals in place
+    KeepOriginals,
+    /// Rewrite the files with microsecond timestamps and delete the originals once committed
+    RemoveOriginals,
+}
+
+impl FromStr for TimestampRewrite {
+    type Err = OxbowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disabled" | "none" => Ok(Self::Disabled),
+            "keep" => Ok(Self::KeepOriginals),
+            "remove" => Ok(Self::RemoveOriginals),
+            other => Err(OxbowError::InvalidConfiguration(format!(
+                "Unknown timestamp rewrite mode `{other}`, expected one of: disabled, keep, remove"
+            ))),
+        }
+    }
+}
+
+/// The outcome of rewriting a set of files
+#[derive(Debug, Clone, Default)]
+pub struct Rewritten {
+    /// The files which should be committed, rewritten or not
+    pub files: Vec<ObjectMeta>,
+    /// The original files which were replaced by a rewritten file
+    pub originals: Vec<ObjectMeta>,
+}
+
+/**
+ * Rewrite any of the given files which contain timestamps without microsecond precision into new
+ * files alongside the originals.
+ *
+ * Files which do not need to be rewritten are passed through as they are. An original and its
+ * rewritten copy, when both are given, only result in the copy once.
+ */
+pub async fn rewrite_timestamps(
+    files: &[ObjectMeta],
+    footers: &FooterCache,
+) -> OxbowResult<Rewritten> {
+    let mut result = Rewritten::default();
+    let mut seen = HashSet::new();
+    let store = footers.store();
+
+    for (file, footer) in files.iter().zip(footers.footers(files).await) {
+        let schema = footer?.schema.clone();
+        let target = with_microsecond_timestamps(&schema);
+
+        if target == schema {
+            if seen.insert(file.location.clone()) {
+                result.files.push(file.clone());
+            }
+            continue;
+        }
+
+        info!("Rewriting {} with microsecond timestamps", file.location);
+        let rewritten = rewrite_file(file, &target, store.clone()).await?;
+        if seen.insert(rewritten.location.clone()) {
+            result.files.push(rewritten);
+        }
+        result.originals.push(file.clone());
+    }
+    Ok(result)
+}
+
+/**
+ * Return the files without the originals whose rewritten copies are among them, since the copies
+ * are what a table tracks in their place. Rewritten copies are kept whatever the files' contents.
+ */
+pub fn without_rewritten_originals(files: Vec<ObjectMeta>) -> Vec<ObjectMeta> {
+    let locations: HashSet<Path> = files.iter().map(|f| f.location.clone()).collect();
+    files
+        .into_iter()
+        .filter(|file| {
+            let rewritten = rewritten_location(&file.location);
+            let replaced = rewritten != file.location && locations.contains(&rewritten);
+            if replaced {
+                debug!(
+                    "Ignoring {} which has been rewritten to {rewritten}",
+                    file.location
+                );
+            }
+            !replaced
+        })
+        .collect()
+}
+
+/**
+ * Delete the original files which have been replaced by rewritten ones.
+ *
+ * This should only be called once the rewritten files have been committed, failures are logged
+ * rather than returned since the table is already consistent at that point.
+ */
+pub async fn remove_originals(originals: &[ObjectMeta], store: Arc<DeltaObjectStore>) {
+    for original in originals.iter() {
+        match store.delete(&original.location).await {
+            Ok(_) => debug!("Removed the original file {}", original.location),
+            Err(err) => warn!(
+                "Failed to remove the original file {} after rewriting: {err:?}",
+                original.location
+            ),
+        }
+    }
+}
+
+/**
+ * Return the location a rewritten copy of the file should be written to
+ */
+fn rewritten_location(location: &Path) -> Path {
+    let location = location.as_ref();
+    let stem = location.strip_suffix(".parquet").unwrap_or(location);
+    Path::from(format!("{stem}.micros.parquet"))
+}
+
+/// The number of bytes the rewritten file buffers before uploading them as the next part
+const REWRITE_BUFFER_SIZE: usize = 10 * 1024 * 1024;
+
+/**
+ * Rewrite the file to the target schema one record batch at a time, uploading the result in parts
+ * so that neither the original nor the rewritten file is ever held in memory as a whole
+ */
+async fn rewrite_file(
+    file: &ObjectMeta,
+    target: &ArrowSchema,
+    store: Arc<DeltaObjectStore>,
+) -> OxbowResult<ObjectMeta> {
+    let location = rewritten_location(&file.location);
+    let (multipart_id, upload) = store.put_multipart(&location).await?;
+
+    let written: OxbowResult<()> = async {
+        let reader = ParquetObjectReader::new(store.clone(), file.clone());
+        let mut stream = ParquetRecordBatchStreamBuilder::new(reader)
+            .await?
+            .build()?;
+        let target = Arc::new(target.clone());
+
+        let mut writer =
+            AsyncArrowWriter::try_new(upload, target.clone(), REWRITE_BUFFER_SIZE, None)?;
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let columns = batch
+                .columns()
+                .iter()
+                .zip(target.fields().iter())
+                .map(|(column, field)| cast_column(column, field.data_type()))
+                .collect::<OxbowResult<Vec<_>>>()?;
+            writer
+                .write(&RecordBatch::try_new(target.clone(), columns)?)
+                .await?;
+        }
+        writer.close().await?;
+        Ok(())
+    }
+    .await;
+
+    if let Err(err) = written {
+        if let Err(abort) = store.abort_multipart(&location, &multipart_id).await {
+            warn!("Failed to abort the upload of {location}: {abort:?}");
+        }
+        return Err(err);
+    }
+    Ok(store.head(&location).await?)
+}
+
+/**
+ * Cast the column to the given type, walking into the structs, lists and maps which arrow cannot
+ * cast directly however deeply they are nested
+ */
+fn cast_column(column: &ArrayRef, data_type: &DataType) -> OxbowResult<ArrayRef> {
+    if column.data_type() == data_type {
+        return Ok(column.clone());
+    }
+
+    match (column.data_type(), data_type) {
+        (DataType::Struct(_), DataType::Struct(fields)) => {
+            let array = column
+                .as_any()
+                .downcast_ref::<StructArray>()
+                .expect("A struct column must be a StructArray");
+            Ok(Arc::new(cast_struct(array, fields)?))
+        }
+        (DataType::List(_), DataType::List(item)) => {
+            let array = column
+                .as_any()
+                .downcast_ref::<ListArray>()
+                .expect("A list column must be a ListArray");
+            Ok(Arc::new(ListArray::try_new(
+                item.clone(),
+                array.offsets().clone(),
+                cast_column(array.values(), item.data_type())?,
+                array.nulls().cloned(),
+            )?))
+        }
+        (DataType::LargeList(_), DataType::LargeList(item)) => {
+            let array = column
+                .as_any()
+                .downcast_ref::<LargeListArray>()
+                .expect("A large list column must be a LargeListArray");
+            Ok(Arc::new(LargeListArray::try_new(
+                item.clone(),
+                array.offsets().clone(),
+                cast_column(array.values(), item.data_type())?,
+                array.nulls().cloned(),
+            )?))
+        }
+        (DataType::Map(_, _), DataType::Map(entries, sorted)) => {
+            let array = column
+                .as_any()
+                .downcast_ref::<MapArray>()
+                .expect("A map column must be a MapArray");
+            let DataType::Struct(fields) = entries.data_type() else {
+                return Ok(cast(column, data_type)?);
+            };
+            Ok(Arc::new(MapArray::try_new(
+                entries.clone(),
+                array.offsets().clone(),
+                cast_struct(array.entries(), fields)?,
+                array.nulls().cloned(),
+                *sorted,
+            )?))
+        }
+        _ => Ok(cast(column, data_type)?),
+    }
+}
+
+fn cast_struct(array: &StructArray, fields: &Fields) -> OxbowResult<StructArray> {
+    let columns = array
+        .columns()
+        .iter()
+        .zip(fields.iter())
+        .map(|(child, field)| cast_column(child, field.data_type()))
+        .collect::<OxbowResult<Vec<_>>>()?;
+    Ok(StructArray::try_new(
+        fields.clone(),
+        columns,
+        array.nulls().cloned(),
+    )?)
+}
+
+/**
+ * Return the schema with every timestamp, however deeply nested, changed to microseconds
+ */
+fn with_microsecond_timestamps(schema: &ArrowSchema) -> ArrowSchema {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|f| microsecond_field(f))
+        .collect();
+    ArrowSchema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+fn microsecond_field(field: &Field) -> Field {
+    field
+        .clone()
+        .with_data_type(microsecond_type(field.data_type()))
+}
+
+fn microsecond_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Timestamp(_, tz) => DataType::Timestamp(TimeUnit::Microsecond, tz.clone()),
+        DataType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(|f| microsecond_field(f))
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        DataType::List(item) => DataType::List(Arc::new(microsecond_field(item))),
+        DataType::LargeList(item) => DataType::LargeList(Arc::new(microsecond_field(item))),
+        DataType::Map(entries, sorted) => {
+            DataType::Map(Arc::new(microsecond_field(entries)), *sorted)
+        }
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rewrite_modes() {
+        assert_eq!(
+            TimestampRewrite::Disabled,
+            "disabled".parse::<TimestampRewrite>().unwrap()
+        );
+        assert_eq!(
+            TimestampRewrite::KeepOriginals,
+            "keep".parse::<TimestampRewrite>().unwrap()
+        );
+        assert_eq!(
+            TimestampRewrite::RemoveOriginals,
+            "Remove".parse::<TimestampRewrite>().unwrap()
+        );
+        assert!("sometimes".parse::<TimestampRewrite>().is_err());
+    }
+
+    #[test]
+    fn rewritten_location_keeps_partitions() {
+        assert_eq!(
+            Path::from("ds=2023-01-01/part-0.micros.parquet"),
+            rewritten_location(&Path::from("ds=2023-01-01/part-0.parquet"))
+        );
+    }
+
+    #[test]
+    fn originals_are_replaced_by_rewritten_files() {
+        let files: Vec<ObjectMeta> = [
+            "ds=1/a.parquet",
+            "ds=1/a.micros.parquet",
+            "ds=1/b.parquet",
+            "ds=2/a.parquet",
+        ]
+        .iter()
+        .map(|path| ObjectMeta {
+            location: Path::from(*path),
+            last_modified: Default::default(),
+            size: 1,
+            e_tag: None,
+        })
+        .collect();
+        assert_eq!(
+            vec!["ds=1/a.micros.parquet", "ds=1/b.parquet", "ds=2/a.parquet"],
+            without_rewritten_originals(files)
+                .iter()
+                .map(|f| f.location.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn nested_timestamps_are_changed() {
+        let schema = ArrowSchema::new(vec![
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new(
+                "nested",
+                DataType::Struct(
+                    vec![Field::new(
+                        "ts",
+                        DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                        true,
+                    )]
+                    .into(),
+                ),
+                true,
+            ),
+        ]);
+        let expected = ArrowSchema::new(vec![
+            Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+            Field::new(
+                "nested",
+                DataType::Struct(
+                    vec![Field::new(
+                        "ts",
+                        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                        true,
+                    )]
+                    .into(),
+                ),
+                true,
+            ),
+        ]);
+        assert_eq!(expected, with_microsecond_timestamps(&schema));
+    }
+
+    #[test]
+    fn timestamps_in_lists_of_structs_are_cast() {
+        use deltalake::arrow::array::TimestampMillisecondArray;
+        use deltalake::arrow::buffer::OffsetBuffer;
+
+        let millis = DataType::Timestamp(TimeUnit::Millisecond, None);
+        let inner = StructArray::try_new(
+            vec![Field::new("ts", millis.clone(), true)].into(),
+            vec![Arc::new(TimestampMillisecondArray::from(vec![1_000, 2_000])) as ArrayRef],
+            None,
+        )
+        .unwrap();
+        let item = Arc::new(Field::new("item", inner.data_type().clone(), true));
+        let column: ArrayRef = Arc::new(
+            ListArray::try_new(
+                item.clone(),
+                OffsetBuffer::from_lengths([2]),
+                Arc::new(inner),
+                None,
+            )
+            .unwrap(),
+        );
+
+        let target = microsecond_type(column.data_type());
+        let cast = cast_column(&column, &target).unwrap();
+        assert_eq!(&target, cast.data_type());
+
+        let list = cast.as_any().downcast_ref::<ListArray>().unwrap();
+        let values = list
+            .values()
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        let ts = values
+            .column(0)
+            .as_any()
+            .downcast_ref::<deltalake::arrow::array::TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(vec![1_000_000, 2_000_000], ts.values().to_vec());
+    }
+}
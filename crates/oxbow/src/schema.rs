@@ -0,0 +1,466 @@
+/*
+ * The schema module contains the logic for inferring a single table schema from the footers of a
+ * number of different parquet files
+ */
+use deltalake::arrow::datatypes::{DataType, Field, FieldRef, Fields, Schema as ArrowSchema};
+use deltalake::{ObjectMeta, Path};
+use tracing::log::*;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{OxbowError, OxbowResult};
+use crate::footers::FooterCache;
+
+/// The result of unifying the schemas of a set of parquet files
+#[derive(Debug, Clone)]
+pub struct UnifiedSchema {
+    /// The merged schema, which every compatible file can be read with
+    pub schema: ArrowSchema,
+    /// Files whose schema could not be merged with the others, along with the reason
+    pub incompatible: Vec<(Path, String)>,
+}
+
+/**
+ * Read the footers of the given files and merge their schemas into one.
+ *
+ * The footers are read concurrently through the [FooterCache]. When `sample` is set, only that
+ * many files, spread evenly across the set, will be read. Files which add columns will have those
+ * columns merged in as nullable, and numeric columns will be widened where that can be done
+ * without losing information. Each column's type is decided by the files which most agree on it,
+ * so any file which cannot be reconciled with that is reported in [UnifiedSchema::incompatible]
+ * rather than merged, whichever order the files are in.
+ */
+pub async fn unify_schemas(
+    files: &[ObjectMeta],
+    footers: &FooterCache,
+    sample: Option<usize>,
+) -> OxbowResult<UnifiedSchema> {
+    let mut merged: Option<ArrowSchema> = None;
+    let mut incompatible = vec![];
+
+    let sampled: Vec<ObjectMeta> = sample_of(files, sample).into_iter().cloned().collect();
+    let mut schemas = vec![];
+    for (file, footer) in sampled.iter().zip(footers.footers(&sampled).await) {
+        match footer {
+            Ok(footer) => {
+                debug!("Read schema from {}: {:?}", file.location, footer.schema);
+                schemas.push((file, footer.schema.clone()));
+            }
+            Err(err) => {
+                warn!("Failed to read the schema of {}: {err:?}", file.location);
+                incompatible.push((file.location.clone(), err.to_string()));
+            }
+        }
+    }
+
+    let candidates = candidate_types(schemas.iter().map(|(_, schema)| schema));
+    for (file, schema) in schemas.iter() {
+        let conflict = conflict_with(&candidates, schema).or_else(|| {
+            let existing = merged.as_ref()?;
+            merge_fields(existing.fields(), schema.fields()).err()
+        });
+        if let Some(reason) = conflict {
+            warn!(
+                "The schema of {} is not compatible with the other files: {reason}",
+                file.location
+            );
+            incompatible.push((file.location.clone(), reason));
+            continue;
+        }
+
+        merged = Some(match merged {
+            None => schema.clone(),
+            Some(existing) => merge_schemas(&existing, schema)?,
+        });
+    }
+
+    match merged {
+        Some(schema) => Ok(UnifiedSchema {
+            schema,
+            incompatible,
+        }),
+        None => Err(OxbowError::SchemaInference(
+            "Failed to read a schema from any of the parquet files".into(),
+        )),
+    }
+}
+
+/**
+ * Pick the type of each column from the type the most schemas have for it, widened with the
+ * column's other types wherever they can be merged. Ties go to the type which sorts first by
+ * name, so the result does not depend on the order of the schemas.
+ */
+fn candidate_types<'a>(
+    schemas: impl Iterator<Item = &'a ArrowSchema>,
+) -> HashMap<&'a str, DataType> {
+    let mut counts: HashMap<&str, HashMap<&DataType, usize>> = HashMap::new();
+    for schema in schemas {
+        for field in schema.fields() {
+            *counts
+                .entry(field.name().as_str())
+                .or_default()
+                .entry(field.data_type())
+                .or_default() += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(column, types)| {
+            let mut types: Vec<(&DataType, usize)> = types.into_iter().collect();
+            types.sort_by(|(left, left_count), (right, right_count)| {
+                right_count
+                    .cmp(left_count)
+                    .then_with(|| left.to_string().cmp(&right.to_string()))
+            });
+            let mut candidate = types[0].0.clone();
+            for (data_type, _) in types.iter().skip(1) {
+                if let Some(merged) = merge_types(&candidate, data_type) {
+                    candidate = merged;
+                }
+            }
+            (column, candidate)
+        })
+        .collect()
+}
+
+/**
+ * Return a description of the first column of the schema which cannot be merged into its
+ * candidate type, if there is one
+ */
+fn conflict_with(candidates: &HashMap<&str, DataType>, schema: &ArrowSchema) -> Option<String> {
+    schema.fields().iter().find_map(|field| {
+        let candidate = candidates.get(field.name().as_str())?;
+        match merge_types(candidate, field.data_type()) {
+            Some(_) => None,
+            None => Some(format!(
+                "column `{}` has the type {} which cannot be merged with {}",
+                field.name(),
+                field.data_type(),
+                candidate
+            )),
+        }
+    })
+}
+
+/**
+ * Pick the files to read schemas from.
+ *
+ * A sample always starts with the smallest file, since it is the cheapest to read, and is then
+ * filled out with files spread evenly across the set
+ */
+fn sample_of(files: &[ObjectMeta], sample: Option<usize>) -> Vec<&ObjectMeta> {
+    match sample {
+        Some(size) if size > 0 && size < files.len() => {
+            let mut sampled: Vec<&ObjectMeta> =
+                crate::find_smallest_file(files).into_iter().collect();
+            for file in files.iter().step_by(files.len() / size) {
+                if sampled.len() >= size {
+                    break;
+                }
+                if !sampled.iter().any(|s| s.location == file.location) {
+                    sampled.push(file);
+                }
+            }
+            sampled
+        }
+        _ => files.iter().collect(),
+    }
+}
+
+/**
+ * Merge the two schemas together, returning a description of the conflict if they cannot be
+ */
+pub fn merge_schemas(left: &ArrowSchema, right: &ArrowSchema) -> OxbowResult<ArrowSchema> {
+    let fields =
+        merge_fields(left.fields(), right.fields()).map_err(OxbowError::SchemaInference)?;
+    let mut metadata = left.metadata().clone();
+    metadata.extend(right.metadata().clone());
+    Ok(ArrowSchema::new_with_metadata(fields, metadata))
+}
+
+fn merge_fields(left: &Fields, right: &Fields) -> Result<Vec<FieldRef>, String> {
+    let mut merged: Vec<FieldRef> = vec![];
+
+    for field in left.iter() {
+        match right.find(field.name()) {
+            Some((_, other)) => merged.push(Arc::new(merge_field(field, other)?)),
+            // Columns missing from some files will be null for those files
+            None => merged.push(Arc::new(field.as_ref().clone().with_nullable(true))),
+        }
+    }
+
+    for field in right.iter() {
+        if left.find(field.name()).is_none() {
+            merged.push(Arc::new(field.as_ref().clone().with_nullable(true)));
+        }
+    }
+    Ok(merged)
+}
+
+fn merge_field(left: &Field, right: &Field) -> Result<Field, String> {
+    let data_type = merge_types(left.data_type(), right.data_type()).ok_or_else(|| {
+        format!(
+            "column `{}` has the type {} which cannot be merged with {}",
+            left.name(),
+            right.data_type(),
+            left.data_type()
+        )
+    })?;
+    Ok(Field::new(
+        left.name(),
+        data_type,
+        left.is_nullable() || right.is_nullable(),
+    )
+    .with_metadata(left.metadata().clone()))
+}
+
+/**
+ * Return the type which can represent values of both types, if there is one
+ */
+fn merge_types(left: &DataType, right: &DataType) -> Option<DataType> {
+    use DataType::*;
+
+    if left == right {
+        return Some(left.clone());
+    }
+
+    match (left, right) {
+        (Struct(left), Struct(right)) => merge_fields(left, right).ok().map(|f| Struct(f.into())),
+        (List(left), List(right)) => Some(List(Arc::new(merge_field(left, right).ok()?))),
+        (LargeList(left), LargeList(right)) => {
+            Some(LargeList(Arc::new(merge_field(left, right).ok()?)))
+        }
+        (l, r) if integer_rank(l).is_some() && integer_rank(r).is_some() => {
+            let (l_signed, l_rank) = integer_rank(l)?;
+            let (r_signed, r_rank) = integer_rank(r)?;
+            if l_signed == r_signed {
+                Some(if l_rank >= r_rank {
+                    l.clone()
+                } else {
+                    r.clone()
+                })
+            } else {
+                // Mixing signedness requires a signed type wider than the unsigned one
+                let (signed, unsigned) = if l_signed { (l, r) } else { (r, l) };
+                let (_, signed_rank) = integer_rank(signed)?;
+                let (_, unsigned_rank) = integer_rank(unsigned)?;
+                let rank = std::cmp::max(signed_rank, unsigned_rank + 1);
+                [Int8, Int16, Int32, Int64].get(rank).cloned()
+            }
+        }
+        (Float16, Float32) | (Float32, Float16) => Some(Float32),
+        (Float16 | Float32, Float64) | (Float64, Float16 | Float32) => Some(Float64),
+        (Utf8, LargeUtf8) | (LargeUtf8, Utf8) => Some(LargeUtf8),
+        (Binary, LargeBinary) | (LargeBinary, Binary) => Some(LargeBinary),
+        (Date32, Date64) | (Date64, Date32) => Some(Date64),
+        _ => None,
+    }
+}
+
+/**
+ * Return the signedness and relative width of an integer type
+ */
+fn integer_rank(data_type: &DataType) -> Option<(bool, usize)> {
+    use DataType::*;
+
+    match data_type {
+        Int8 => Some((true, 0)),
+        Int16 => Some((true, 1)),
+        Int32 => Some((true, 2)),
+        Int64 => Some((true, 3)),
+        UInt8 => Some((false, 0)),
+        UInt16 => Some((false, 1)),
+        UInt32 => Some((false, 2)),
+        UInt64 => Some((false, 3)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema_of(fields: Vec<Field>) -> ArrowSchema {
+        ArrowSchema::new(fields)
+    }
+
+    #[test]
+    fn merge_identical_schemas() {
+        let schema = schema_of(vec![Field::new("id", DataType::Int64, false)]);
+        let merged = merge_schemas(&schema, &schema).expect("Failed to merge");
+        assert_eq!(schema, merged);
+    }
+
+    #[test]
+    fn merge_added_column_is_nullable() {
+        let left = schema_of(vec![Field::new("id", DataType::Int64, false)]);
+        let right = schema_of(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+
+        let merged = merge_schemas(&left, &right).expect("Failed to merge");
+        assert_eq!(2, merged.fields().len());
+        let (_, name) = merged.fields().find("name").expect("Missing merged column");
+        assert!(name.is_nullable(), "Added columns must be nullable");
+        let (_, id) = merged.fields().find("id").expect("Missing original column");
+        assert!(!id.is_nullable());
+    }
+
+    #[test]
+    fn merge_missing_column_is_nullable() {
+        let left = schema_of(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let right = schema_of(vec![Field::new("id", DataType::Int64, false)]);
+
+        let merged = merge_schemas(&left, &right).expect("Failed to merge");
+        let (_, name) = merged.fields().find("name").expect("Missing merged column");
+        assert!(name.is_nullable());
+    }
+
+    #[test]
+    fn merge_widened_types() {
+        let left = schema_of(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("score", DataType::Float32, true),
+            Field::new("count", DataType::UInt32, true),
+        ]);
+        let right = schema_of(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("score", DataType::Float64, true),
+            Field::new("count", DataType::Int32, true),
+        ]);
+
+        let merged = merge_schemas(&left, &right).expect("Failed to merge");
+        assert_eq!(
+            &DataType::Int64,
+            merged.field_with_name("id").unwrap().data_type()
+        );
+        assert_eq!(
+            &DataType::Float64,
+            merged.field_with_name("score").unwrap().data_type()
+        );
+        assert_eq!(
+            &DataType::Int64,
+            merged.field_with_name("count").unwrap().data_type()
+        );
+    }
+
+    #[test]
+    fn merge_nested_structs() {
+        let left = schema_of(vec![Field::new(
+            "nested",
+            DataType::Struct(vec![Field::new("a", DataType::Int32, true)].into()),
+            true,
+        )]);
+        let right = schema_of(vec![Field::new(
+            "nested",
+            DataType::Struct(
+                vec![
+                    Field::new("a", DataType::Int64, true),
+                    Field::new("b", DataType::Utf8, true),
+                ]
+                .into(),
+            ),
+            true,
+        )]);
+
+        let merged = merge_schemas(&left, &right).expect("Failed to merge");
+        let expected = DataType::Struct(
+            vec![
+                Field::new("a", DataType::Int64, true),
+                Field::new("b", DataType::Utf8, true),
+            ]
+            .into(),
+        );
+        assert_eq!(
+            &expected,
+            merged.field_with_name("nested").unwrap().data_type()
+        );
+    }
+
+    #[test]
+    fn merge_incompatible_types() {
+        let left = schema_of(vec![Field::new("id", DataType::Int64, false)]);
+        let right = schema_of(vec![Field::new("id", DataType::Utf8, false)]);
+
+        let result = merge_schemas(&left, &right);
+        assert!(result.is_err(), "Should not merge a string into an integer");
+    }
+
+    #[test]
+    fn sample_of_starts_with_smallest() {
+        use chrono::prelude::Utc;
+
+        let files: Vec<ObjectMeta> = (0..10)
+            .map(|i| ObjectMeta {
+                location: Path::from(format!("file-{i}.parquet")),
+                last_modified: Utc::now(),
+                size: 100 - i,
+                e_tag: None,
+            })
+            .collect();
+
+        let sampled = sample_of(&files, Some(3));
+        assert_eq!(3, sampled.len());
+        assert_eq!(Path::from("file-9.parquet"), sampled[0].location);
+
+        assert_eq!(10, sample_of(&files, None).len());
+        assert_eq!(10, sample_of(&files, Some(20)).len());
+    }
+
+    #[tokio::test]
+    async fn unify_schemas_regardless_of_order() {
+        use deltalake::arrow::array::{Int32Array, StringArray};
+        use deltalake::arrow::record_batch::RecordBatch;
+        use deltalake::ObjectStore;
+
+        let (dir, store) = crate::tests::util::create_empty_temp_path();
+        let ints = RecordBatch::try_new(
+            Arc::new(schema_of(vec![Field::new("id", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let strings = RecordBatch::try_new(
+            Arc::new(schema_of(vec![Field::new("id", DataType::Utf8, false)])),
+            vec![Arc::new(StringArray::from(vec!["one"]))],
+        )
+        .unwrap();
+        crate::tests::util::write_parquet(dir.path(), "a-odd.parquet", &strings);
+        crate::tests::util::write_parquet(dir.path(), "b.parquet", &ints);
+        crate::tests::util::write_parquet(dir.path(), "c.parquet", &ints);
+
+        let mut files = vec![];
+        for name in ["a-odd.parquet", "b.parquet", "c.parquet"] {
+            files.push(store.head(&Path::from(name)).await.unwrap());
+        }
+        let footers = FooterCache::new(store, 4, Default::default());
+
+        // The odd one out is read first, then last, and is the one reported either way
+        for _ in 0..2 {
+            let unified = unify_schemas(&files, &footers, None)
+                .await
+                .expect("Failed to unify schemas");
+            assert_eq!(
+                &DataType::Int32,
+                unified.schema.field_with_name("id").unwrap().data_type()
+            );
+            let incompatible: Vec<&Path> = unified.incompatible.iter().map(|(p, _)| p).collect();
+            assert_eq!(vec![&Path::from("a-odd.parquet")], incompatible);
+            files.reverse();
+        }
+    }
+
+    #[test]
+    fn merge_unsigned_without_wider_signed() {
+        let left = schema_of(vec![Field::new("id", DataType::UInt64, false)]);
+        let right = schema_of(vec![Field::new("id", DataType::Int8, false)]);
+
+        let result = merge_schemas(&left, &right);
+        assert!(result.is_err(), "There is no signed type wider than u64");
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * The spark module contains the conventions Spark, Hadoop and Databricks jobs use to mark output
+ * which is not (yet) part of a dataset, so that discovery does not pick up half-written or aborted
+ * task files
+ */
+use deltalake::storage::DeltaObjectStore;
+use deltalake::{ObjectMeta, ObjectStore, Path};
+use serde::Deserialize;
+use tracing::log::*;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::error::OxbowResult;
+
+/// Prefix of the marker files Databricks' DBIO commit protocol writes for a committed job
+const COMMITTED_MARKER: &str = "_committed_";
+
+/**
+ * Return true if any segment of the path is hidden by Hadoop's conventions.
+ *
+ * Segments starting with `.` are hidden, e.g. `.spark-staging-1`, as are segments starting with
+ * `_`, e.g. `_temporary`, unless they look like a hive-style partition such as `_c0=1`.
+ */
+pub fn is_hidden(path: &Path) -> bool {
+    path.parts().any(|part| {
+        let part = part.as_ref();
+        part.starts_with('.')
+            || (part.starts_with('_') && !part.contains('='))
+            || part.ends_with("._COPYING_")
+    })
+}
+
+/**
+ * Return true if the path is a DBIO commit marker
+ */
+pub fn is_committed_marker(path: &Path) -> bool {
+    path.filename()
+        .map(|name| name.starts_with(COMMITTED_MARKER))
+        .unwrap_or(false)
+}
+
+/**
+ * Return the DBIO transaction id of a data file written by Databricks, e.g. the `123` in
+ * `part-00000-tid-123-a1b2-c000.snappy.parquet`
+ */
+pub fn dbio_tid(path: &Path) -> Option<&str> {
+    let filename = path.filename()?;
+    let (_, rest) = filename.split_once("-tid-")?;
+    let tid = rest.split('-').next()?;
+    if !tid.is_empty() && tid.chars().all(|c| c.is_ascii_digit()) {
+        Some(tid)
+    } else {
+        None
+    }
+}
+
+/// The contents of a `_committed_<tid>` marker
+#[derive(Debug, Default, Deserialize)]
+struct CommitMarker {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+/**
+ * Return only the files which are not DBIO output, or are DBIO output listed as added by a
+ * `_committed_<tid>` marker in the same directory and not removed by a later one.
+ *
+ * Markers which cannot be read or parsed are treated as missing, so their files are left out.
+ */
+pub async fn committed_files(
+    files: Vec<ObjectMeta>,
+    markers: &[ObjectMeta],
+    store: Arc<DeltaObjectStore>,
+) -> OxbowResult<Vec<ObjectMeta>> {
+    if !files.iter().any(|f| dbio_tid(&f.location).is_some()) {
+        return Ok(files);
+    }
+
+    // The committed and removed file names, keyed by the directory holding the marker
+    let mut added: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut removed: HashMap<String, HashSet<String>> = HashMap::new();
+
+    for marker in markers.iter() {
+        let directory = directory_of(&marker.location);
+        let contents = match read_marker(marker, store.clone()).await {
+            Ok(contents) => contents,
+            Err(err) => {
+                warn!("Failed to read the DBIO marker {}: {err}", marker.location);
+                continue;
+            }
+        };
+        added
+            .entry(directory.clone())
+            .or_default()
+            .extend(contents.added);
+        removed
+            .entry(directory)
+            .or_default()
+            .extend(contents.removed);
+    }
+
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            if dbio_tid(&file.location).is_none() {
+                return true;
+            }
+            let directory = directory_of(&file.location);
+            let name = file.location.filename().unwrap_or_default();
+            let listed = |names: &HashMap<String, HashSet<String>>| {
+                names
+                    .get(&directory)
+                    .map(|names| names.contains(name))
+                    .unwrap_or(false)
+            };
+            let committed = listed(&added) && !listed(&removed);
+            if !committed {
+                debug!("Ignoring uncommitted DBIO output: {}", file.location);
+            }
+            committed
+        })
+        .collect())
+}
+
+async fn read_marker(
+    marker: &ObjectMeta,
+    store: Arc<DeltaObjectStore>,
+) -> OxbowResult<CommitMarker> {
+    let bytes = store.get(&marker.location).await?.bytes().await?;
+    parse_marker(&String::from_utf8_lossy(&bytes))
+        .map_err(|err| deltalake::DeltaTableError::Generic(err.to_string()).into())
+}
+
+/**
+ * Parse the contents of a marker, which is a JSON object preceded by a version line like `v1`
+ */
+fn parse_marker(contents: &str) -> Result<CommitMarker, serde_json::Error> {
+    let json = contents
+        .find('{')
+        .map(|start| &contents[start..])
+        .unwrap_or(contents);
+    serde_json::from_str(json)
+}
+
+fn directory_of(path: &Path) -> String {
+    let path = path.as_ref();
+    path.rsplit_once('/')
+        .map(|(dir, _)| dir.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_paths() {
+        assert!(is_hidden(&Path::from("_temporary/0/part-0.parquet")));
+        assert!(is_hidden(&Path::from(
+            "year=2023/.spark-staging-1/a.parquet"
+        )));
+        assert!(is_hidden(&Path::from(".hidden.parquet")));
+        assert!(is_hidden(&Path::from("_SUCCESS")));
+        assert!(is_hidden(&Path::from("a.parquet._COPYING_")));
+        assert!(!is_hidden(&Path::from("year=2023/part-0.parquet")));
+        assert!(!is_hidden(&Path::from("_c0=1/part-0.parquet")));
+    }
+
+    #[test]
+    fn dbio_tids() {
+        assert_eq!(
+            Some("4257463428479391237"),
+            dbio_tid(&Path::from(
+                "ds=1/part-00000-tid-4257463428479391237-7c1d5c4b-c000.snappy.parquet"
+            ))
+        );
+        assert_eq!(
+            None,
+            dbio_tid(&Path::from("part-00000-c000.snappy.parquet"))
+        );
+        assert_eq!(
+            None,
+            dbio_tid(&Path::from("part-00000-tid-abc-c000.parquet"))
+        );
+    }
+
+    #[test]
+    fn parse_versioned_marker() {
+        let marker = parse_marker("v1\n{\"added\":[\"part-0-tid-1-a.parquet\"],\"removed\":[]}")
+            .expect("Failed to parse marker");
+        assert_eq!(vec!["part-0-tid-1-a.parquet".to_string()], marker.added);
+        assert!(marker.removed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_committed_dbio_files() {
+        let (_tempdir, store) = crate::tests::util::create_empty_temp_path();
+        store
+            .put(
+                &Path::from("ds=1/_committed_1"),
+                "v1\n{\"added\":[\"part-0-tid-1-a.parquet\",\"part-1-tid-1-b.parquet\"],\"removed\":[]}"
+                    .into(),
+            )
+            .await
+            .unwrap();
+        store
+            .put(
+                &Path::from("ds=1/_committed_2"),
+                "v1\n{\"added\":[],\"removed\":[\"part-1-tid-1-b.parquet\"]}".into(),
+            )
+            .await
+            .unwrap();
+        let markers = vec![
+            store.head(&Path::from("ds=1/_committed_1")).await.unwrap(),
+            store.head(&Path::from("ds=1/_committed_2")).await.unwrap(),
+        ];
+
+        let file = |name: &str| ObjectMeta {
+            location: Path::from(name),
+            last_modified: chrono::Utc::now(),
+            size: 1,
+            e_tag: None,
+        };
+        let files = vec![
+            file("ds=1/part-0-tid-1-a.parquet"),
+            file("ds=1/part-1-tid-1-b.parquet"),
+            file("ds=1/part-2-tid-3-c.parquet"),
+            file("ds=2/part-0-tid-1-a.parquet"),
+            file("ds=1/plain.parquet"),
+        ];
+
+        let committed = committed_files(files, &markers, store)
+            .await
+            .expect("Failed to filter files");
+        let locations: Vec<&str> = committed.iter().map(|f| f.location.as_ref()).collect();
+        assert_eq!(
+            vec!["ds=1/part-0-tid-1-a.parquet", "ds=1/plain.parquet"],
+            locations
+        );
+    }
+}
@@ -0,0 +1,166 @@
+/*
+ * The sync module reconciles an existing Delta table with the files which are actually in its
+ * location, which recovers tables after bucket notifications have been missed
+ */
+use deltalake::{DeltaTable, ObjectMeta, ObjectStore, Path};
+use futures::StreamExt;
+use serde::Serialize;
+use tracing::log::*;
+
+use std::collections::HashSet;
+
+use crate::error::OxbowResult;
+use crate::ConversionOptions;
+
+/// What a sync changed in the table
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncSummary {
+    /// The parquet files which were in the location but not the table
+    pub added: Vec<String>,
+    /// The files which were in the table but no longer in the location
+    pub removed: Vec<String>,
+    /// The version of the table once the sync has finished
+    pub version: i64,
+}
+
+/**
+ * Sync the table with its location, committing Adds for parquet files which the table does not
+ * track and Removes for files the table tracks which no longer exist.
+ *
+ * Untracked files are found with the same discovery rules, filters and validation as a
+ * conversion, while a tracked file is only removed if it is missing from the store entirely.
+ * Missing files cannot be removed from a table with `delta.appendOnly` set, so the sync fails
+ * with [crate::error::OxbowError::AppendOnlyTable] before committing anything.
+ */
+pub async fn sync_table(
+    table: &mut DeltaTable,
+    options: &ConversionOptions,
+) -> OxbowResult<SyncSummary> {
+    let store = table.object_store();
+    let tracked = table.get_file_set();
+
+    let mut existing = HashSet::new();
+    let mut iter = store.list(None).await?;
+    while let Some(meta) = iter.next().await {
+        existing.insert(meta?.location);
+    }
+    drop(iter);
+
+    let missing: Vec<ObjectMeta> = table
+        .get_state()
+        .files()
+        .iter()
+        .filter_map(|add| {
+            let location = Path::parse(&add.path).unwrap_or_else(|_| Path::from(add.path.as_ref()));
+            (!existing.contains(&location)).then(|| ObjectMeta {
+                location,
+                last_modified: chrono::Utc::now(),
+                size: add.size as usize,
+                e_tag: None,
+            })
+        })
+        .collect();
+
+    let untracked: Vec<ObjectMeta> =
+        crate::discover_parquet_files_with_options(store.clone(), options)
+            .await?
+            .into_iter()
+            .filter(|f| !tracked.contains(&f.location))
+            .collect();
+
+    info!(
+        "Syncing the table found {} untracked and {} missing files",
+        untracked.len(),
+        missing.len()
+    );
+
+    if !missing.is_empty() {
+        crate::remove_from_table_with_options(&missing, table, options).await?;
+        table.update().await?;
+    }
+
+    if !untracked.is_empty() {
+        crate::append_to_table_with_options(&untracked, table, options).await?;
+        table.update().await?;
+    }
+
+    // Validation may have left some of the untracked files out of the table
+    let now_tracked = table.get_file_set();
+    Ok(SyncSummary {
+        added: untracked
+            .iter()
+            .filter(|f| now_tracked.contains(&f.location))
+            .map(|f| f.location.to_string())
+            .collect(),
+        removed: missing.iter().map(|f| f.location.to_string()).collect(),
+        version: table.version(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sync_untracked_and_missing_files() {
+        let (tempdir, store) = crate::tests::util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-non-partitioned",
+        );
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert!(files.len() > 1, "The fixture needs more than one file");
+
+        let mut table = crate::create_table_with(&files[0..1], store.clone())
+            .await
+            .expect("Failed to create table");
+
+        // Every other file is untracked, and the tracked one goes missing
+        std::fs::remove_file(tempdir.path().join(files[0].location.as_ref())).unwrap();
+
+        let summary = sync_table(&mut table, &ConversionOptions::default())
+            .await
+            .expect("Failed to sync");
+        assert_eq!(vec![files[0].location.to_string()], summary.removed);
+        assert_eq!(files.len() - 1, summary.added.len());
+        assert_eq!(2, summary.version);
+        assert_eq!(files.len() - 1, table.get_files().len());
+
+        let summary = sync_table(&mut table, &ConversionOptions::default())
+            .await
+            .expect("Failed to sync");
+        assert!(summary.added.is_empty() && summary.removed.is_empty());
+        assert_eq!(
+            2, summary.version,
+            "An in-sync table should not be committed to"
+        );
+    }
+
+    #[tokio::test]
+    async fn sync_append_only_table_with_missing_files() {
+        let (tempdir, store) = crate::tests::util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-non-partitioned",
+        );
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let options = ConversionOptions {
+            table_properties: std::collections::HashMap::from([(
+                "delta.appendOnly".to_string(),
+                "true".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let mut table = crate::create_table_with_options(&files, store.clone(), &options)
+            .await
+            .expect("Failed to create table");
+
+        std::fs::remove_file(tempdir.path().join(files[0].location.as_ref())).unwrap();
+        let result = sync_table(&mut table, &options).await;
+        assert!(matches!(
+            result,
+            Err(crate::error::OxbowError::AppendOnlyTable { .. })
+        ));
+        assert_eq!(0, table.version());
+    }
+}
@@ -0,0 +1,91 @@
+/*
+ * The transactions module makes commits idempotent with Delta `txn` actions, so that redelivered
+ * batches of events which have already been added or removed are skipped
+ */
+use chrono::prelude::*;
+use deltalake::protocol::{Action, Txn};
+use deltalake::DeltaTable;
+
+/// The application id of the `txn` actions oxbow commits along with added files
+pub const ADD_APP_ID: &str = "oxbow:add";
+
+/// The application id of the `txn` actions oxbow commits along with removed files
+pub const REMOVE_APP_ID: &str = "oxbow:remove";
+
+/**
+ * A Transaction identifies a batch of changes to the table by an application id and a version.
+ *
+ * Every batch of added files shares the [ADD_APP_ID], and every batch of removed files the
+ * [REMOVE_APP_ID], so the table only ever holds one `txn` for each. The version is a sequence
+ * taken from the batch's events, such as the time of the latest one, and a batch is applied once
+ * the table's version for its application id has reached it.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    /// The application the transaction belongs to
+    pub app_id: String,
+    /// The sequence number of the transaction
+    pub version: i64,
+}
+
+impl Transaction {
+    /**
+     * Return the transaction for a batch of added files with the given sequence
+     */
+    pub fn for_adds(version: i64) -> Self {
+        Self {
+            app_id: ADD_APP_ID.into(),
+            version,
+        }
+    }
+
+    /**
+     * Return the transaction for a batch of removed files with the given sequence
+     */
+    pub fn for_removes(version: i64) -> Self {
+        Self {
+            app_id: REMOVE_APP_ID.into(),
+            version,
+        }
+    }
+
+    /**
+     * Return true if the table has already committed this transaction, or a later one of the same
+     * application
+     */
+    pub fn is_applied(&self, table: &DeltaTable) -> bool {
+        table
+            .get_app_transaction_version()
+            .get(&self.app_id)
+            .map(|version| *version >= self.version)
+            .unwrap_or(false)
+    }
+
+    /**
+     * Return the `txn` action to commit along with the batch
+     */
+    pub fn action(&self) -> Action {
+        Action::txn(Txn {
+            app_id: self.app_id.clone(),
+            version: self.version,
+            last_updated: Some(Utc::now().timestamp_millis()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_actions() {
+        match Transaction::for_adds(1702858944292).action() {
+            Action::txn(txn) => {
+                assert_eq!("oxbow:add", txn.app_id);
+                assert_eq!(1702858944292, txn.version);
+            }
+            other => panic!("Expected a txn action, not {other:?}"),
+        }
+        assert_eq!("oxbow:remove", Transaction::for_removes(1).app_id);
+    }
+}
@@ -0,0 +1,423 @@
+/*
+ * The types module maps the Arrow types read from parquet files onto the types which can be
+ * represented in a Delta table schema
+ */
+use deltalake::arrow::datatypes::{
+    DataType, Field, FieldRef, Fields, Schema as ArrowSchema, TimeUnit,
+};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use tracing::log::*;
+
+use std::sync::Arc;
+
+use crate::error::{OxbowError, OxbowResult};
+
+/// The largest decimal precision which Delta supports
+const MAX_DECIMAL_PRECISION: u8 = 38;
+
+/// A column whose type had to be changed to be represented in the Delta schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeCoercion {
+    /// Dotted path to the column, including any parent structs, lists or maps
+    pub column: String,
+    /// The type as read from the parquet file
+    pub from: DataType,
+    /// The type used for the Delta schema
+    pub to: DataType,
+}
+
+impl Serialize for TypeCoercion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TypeCoercion", 3)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("from", &self.from.to_string())?;
+        state.serialize_field("to", &self.to.to_string())?;
+        state.end()
+    }
+}
+
+/**
+ * Map every field of the given Arrow schema, including those nested inside of structs, lists and
+ * maps, onto an Arrow type which has a Delta equivalent.
+ *
+ * The mapped schema is returned along with every coercion which was made. If any column has a
+ * type which cannot be represented in Delta at all, an error listing all of those columns is
+ * returned instead.
+ */
+pub fn delta_compatible_schema(
+    schema: &ArrowSchema,
+) -> OxbowResult<(ArrowSchema, Vec<TypeCoercion>)> {
+    let mut coercions = vec![];
+    let mut unsupported = vec![];
+
+    let fields = map_fields(schema.fields(), "", &mut coercions, &mut unsupported);
+
+    if !unsupported.is_empty() {
+        return Err(OxbowError::UnsupportedTypes {
+            columns: unsupported
+                .into_iter()
+                .map(|(column, data_type)| (column, data_type.to_string()))
+                .collect(),
+        });
+    }
+
+    for coercion in coercions.iter() {
+        debug!(
+            "Coercing the column `{}` from {} to {}",
+            coercion.column, coercion.from, coercion.to
+        );
+    }
+
+    Ok((
+        ArrowSchema::new_with_metadata(fields, schema.metadata().clone()),
+        coercions,
+    ))
+}
+
+fn map_fields(
+    fields: &Fields,
+    parent: &str,
+    coercions: &mut Vec<TypeCoercion>,
+    unsupported: &mut Vec<(String, DataType)>,
+) -> Vec<FieldRef> {
+    fields
+        .iter()
+        .map(|field| Arc::new(map_field(field, parent, coercions, unsupported)))
+        .collect()
+}
+
+fn map_field(
+    field: &Field,
+    parent: &str,
+    coercions: &mut Vec<TypeCoercion>,
+    unsupported: &mut Vec<(String, DataType)>,
+) -> Field {
+    let column = match parent {
+        "" => field.name().to_string(),
+        parent => format!("{parent}.{}", field.name()),
+    };
+    let data_type = map_type(field.data_type(), &column, coercions, unsupported);
+    field.clone().with_data_type(data_type)
+}
+
+/**
+ * Return the Arrow type which should be used for the Delta schema in place of the given type
+ *
+ * Nested types are walked so that their children are mapped too. Types which have no Delta
+ * equivalent are recorded in `unsupported` and returned unchanged.
+ */
+fn map_type(
+    data_type: &DataType,
+    column: &str,
+    coercions: &mut Vec<TypeCoercion>,
+    unsupported: &mut Vec<(String, DataType)>,
+) -> DataType {
+    use DataType::*;
+
+    let coerced = match data_type {
+        Boolean
+        | Int8
+        | Int16
+        | Int32
+        | Int64
+        | Float32
+        | Float64
+        | Utf8
+        | Binary
+        | Date32
+        | Timestamp(TimeUnit::Microsecond, None) => None,
+        Timestamp(TimeUnit::Microsecond, Some(tz)) if tz.eq_ignore_ascii_case("utc") => None,
+        Timestamp(_, tz) => Some(Timestamp(TimeUnit::Microsecond, utc_or_none(tz))),
+        // Following Spark, unsigned integers are widened into the next signed type
+        UInt8 => Some(Int16),
+        UInt16 => Some(Int32),
+        UInt32 => Some(Int64),
+        UInt64 => Some(Decimal128(20, 0)),
+        Float16 => Some(Float32),
+        LargeUtf8 => Some(Utf8),
+        LargeBinary | FixedSizeBinary(_) => Some(Binary),
+        Date64 => Some(Date32),
+        Decimal128(precision, _) | Decimal256(precision, _)
+            if *precision > MAX_DECIMAL_PRECISION =>
+        {
+            unsupported.push((column.to_string(), data_type.clone()));
+            None
+        }
+        Decimal128(_, _) => None,
+        Decimal256(precision, scale) => Some(Decimal128(*precision, *scale)),
+        Dictionary(_, value) => {
+            let mut nested = vec![];
+            let value = map_type(value, column, &mut nested, unsupported);
+            // Coercing the value type itself is covered by unwrapping the dictionary
+            coercions.extend(nested.into_iter().filter(|c| c.column != column));
+            Some(value)
+        }
+        Struct(fields) => {
+            return Struct(map_fields(fields, column, coercions, unsupported).into());
+        }
+        List(item) => {
+            return List(Arc::new(map_field(item, column, coercions, unsupported)));
+        }
+        LargeList(item) | FixedSizeList(item, _) => Some(List(Arc::new(map_field(
+            item,
+            column,
+            coercions,
+            unsupported,
+        )))),
+        Map(entries, sorted) => {
+            return Map(
+                Arc::new(map_field(entries, column, coercions, unsupported)),
+                *sorted,
+            );
+        }
+        _ => {
+            unsupported.push((column.to_string(), data_type.clone()));
+            None
+        }
+    };
+
+    match coerced {
+        Some(to) => {
+            coercions.push(TypeCoercion {
+                column: column.to_string(),
+                from: data_type.clone(),
+                to: to.clone(),
+            });
+            to
+        }
+        None => data_type.clone(),
+    }
+}
+
+/**
+ * Delta timestamps are always adjusted to UTC, so only a UTC timezone is carried over
+ */
+fn utc_or_none(tz: &Option<Arc<str>>) -> Option<Arc<str>> {
+    match tz {
+        Some(tz) if tz.eq_ignore_ascii_case("utc") || tz.as_ref() == "+00:00" => Some("UTC".into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(data_type: DataType) -> OxbowResult<(ArrowSchema, Vec<TypeCoercion>)> {
+        delta_compatible_schema(&ArrowSchema::new(vec![Field::new(
+            "column", data_type, true,
+        )]))
+    }
+
+    fn mapped_type(data_type: DataType) -> DataType {
+        let (schema, _) = map(data_type).expect("Failed to map the type");
+        schema.field(0).data_type().clone()
+    }
+
+    #[test]
+    fn supported_types_are_unchanged() {
+        for data_type in [
+            DataType::Boolean,
+            DataType::Int64,
+            DataType::Utf8,
+            DataType::Date32,
+            DataType::Decimal128(10, 2),
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+        ] {
+            let (schema, coercions) = map(data_type.clone()).expect("Failed to map");
+            assert_eq!(&data_type, schema.field(0).data_type());
+            assert!(coercions.is_empty(), "Unexpected coercion for {data_type}");
+        }
+    }
+
+    #[test]
+    fn timestamps_become_microseconds() {
+        for unit in [
+            TimeUnit::Second,
+            TimeUnit::Millisecond,
+            TimeUnit::Nanosecond,
+        ] {
+            assert_eq!(
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                mapped_type(DataType::Timestamp(unit.clone(), None))
+            );
+            assert_eq!(
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                mapped_type(DataType::Timestamp(unit, Some("+00:00".into())))
+            );
+        }
+    }
+
+    #[test]
+    fn unsigned_integers_are_widened() {
+        assert_eq!(DataType::Int16, mapped_type(DataType::UInt8));
+        assert_eq!(DataType::Int32, mapped_type(DataType::UInt16));
+        assert_eq!(DataType::Int64, mapped_type(DataType::UInt32));
+        assert_eq!(DataType::Decimal128(20, 0), mapped_type(DataType::UInt64));
+    }
+
+    #[test]
+    fn dictionary_and_large_types() {
+        assert_eq!(
+            DataType::Utf8,
+            mapped_type(DataType::Dictionary(
+                Box::new(DataType::Int32),
+                Box::new(DataType::LargeUtf8)
+            ))
+        );
+        assert_eq!(DataType::Binary, mapped_type(DataType::LargeBinary));
+        assert_eq!(DataType::Binary, mapped_type(DataType::FixedSizeBinary(16)));
+    }
+
+    #[test]
+    fn nested_timestamps_are_mapped() {
+        let nested = DataType::Struct(
+            vec![
+                Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+                Field::new(
+                    "list",
+                    DataType::List(Arc::new(Field::new(
+                        "element",
+                        DataType::Timestamp(TimeUnit::Nanosecond, None),
+                        true,
+                    ))),
+                    true,
+                ),
+            ]
+            .into(),
+        );
+        let (schema, coercions) = map(nested).expect("Failed to map");
+        let columns: Vec<&str> = coercions.iter().map(|c| c.column.as_str()).collect();
+        assert_eq!(vec!["column.ts", "column.list.element"], columns);
+
+        let expected = DataType::Struct(
+            vec![
+                Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, None), true),
+                Field::new(
+                    "list",
+                    DataType::List(Arc::new(Field::new(
+                        "element",
+                        DataType::Timestamp(TimeUnit::Microsecond, None),
+                        true,
+                    ))),
+                    true,
+                ),
+            ]
+            .into(),
+        );
+        assert_eq!(&expected, schema.field(0).data_type());
+    }
+
+    #[test]
+    fn coercions_inside_large_and_fixed_size_lists() {
+        let item = Arc::new(Field::new(
+            "element",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        ));
+        for data_type in [
+            DataType::LargeList(item.clone()),
+            DataType::FixedSizeList(item.clone(), 2),
+        ] {
+            let (schema, coercions) = map(data_type).expect("Failed to map");
+            let columns: Vec<&str> = coercions.iter().map(|c| c.column.as_str()).collect();
+            assert_eq!(vec!["column.element", "column"], columns);
+            assert_eq!(
+                &DataType::List(Arc::new(Field::new(
+                    "element",
+                    DataType::Timestamp(TimeUnit::Microsecond, None),
+                    true,
+                ))),
+                schema.field(0).data_type()
+            );
+        }
+    }
+
+    #[test]
+    fn coercions_inside_dictionaries() {
+        let value = DataType::Struct(vec![Field::new("id", DataType::UInt32, true)].into());
+        let (_, coercions) = map(DataType::Dictionary(
+            Box::new(DataType::Int32),
+            Box::new(value),
+        ))
+        .expect("Failed to map");
+        let columns: Vec<&str> = coercions.iter().map(|c| c.column.as_str()).collect();
+        assert_eq!(vec!["column.id", "column"], columns);
+        assert_eq!(DataType::Int64, coercions[0].to);
+    }
+
+    #[test]
+    fn int96_timestamps_become_microseconds() {
+        use deltalake::parquet::arrow::parquet_to_arrow_schema;
+        use deltalake::parquet::schema::{parser::parse_message_type, types::SchemaDescriptor};
+
+        let message = parse_message_type("message spark { optional int96 ts; }")
+            .expect("Failed to parse the parquet schema");
+        let schema = parquet_to_arrow_schema(&SchemaDescriptor::new(Arc::new(message)), None)
+            .expect("Failed to convert the parquet schema");
+
+        let (schema, coercions) = delta_compatible_schema(&schema).expect("Failed to map");
+        assert_eq!(
+            &DataType::Timestamp(TimeUnit::Microsecond, None),
+            schema.field(0).data_type()
+        );
+        assert_eq!(1, coercions.len());
+        assert_eq!(
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            coercions[0].from
+        );
+    }
+
+    #[test]
+    fn timestamps_inside_maps() {
+        let entries = Field::new(
+            "key_value",
+            DataType::Struct(
+                vec![
+                    Field::new("key", DataType::Utf8, false),
+                    Field::new(
+                        "value",
+                        DataType::Timestamp(TimeUnit::Nanosecond, None),
+                        true,
+                    ),
+                ]
+                .into(),
+            ),
+            false,
+        );
+        let (_, coercions) = map(DataType::Map(Arc::new(entries), false)).expect("Failed to map");
+        assert_eq!(1, coercions.len());
+        assert_eq!("column.key_value.value", coercions[0].column);
+    }
+
+    #[test]
+    fn null_type_is_unsupported() {
+        assert!(map(DataType::Null).is_err());
+    }
+
+    #[test]
+    fn unsupported_types_are_listed() {
+        let schema = ArrowSchema::new(vec![
+            Field::new("time", DataType::Time32(TimeUnit::Millisecond), true),
+            Field::new("id", DataType::Int64, true),
+            Field::new(
+                "nested",
+                DataType::Struct(
+                    vec![Field::new(
+                        "duration",
+                        DataType::Duration(TimeUnit::Second),
+                        true,
+                    )]
+                    .into(),
+                ),
+                true,
+            ),
+        ]);
+
+        let result = delta_compatible_schema(&schema);
+        let message = result.expect_err("Should have failed").to_string();
+        assert!(message.contains("`time`"), "{message}");
+        assert!(message.contains("`nested.duration`"), "{message}");
+        assert!(!message.contains("`id`"), "{message}");
+    }
+}
@@ -0,0 +1,420 @@
+/*
+ * The validate module checks that parquet files are readable and compatible with a table before
+ * they are committed, and quarantines the ones which are not so that a single bad upload cannot
+ * break every reader of the table
+ */
+use deltalake::parquet::file::metadata::ParquetMetaData;
+use deltalake::schema::Schema;
+use deltalake::storage::DeltaObjectStore;
+use deltalake::{ObjectMeta, ObjectStore, Path, SchemaDataType};
+use serde::Serialize;
+use tracing::log::*;
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::{OxbowError, OxbowResult};
+use crate::footers::FooterCache;
+
+/// The prefix, relative to the table location, quarantined files are moved under
+pub const QUARANTINE_PREFIX: &str = "_quarantine";
+
+/// The suffix of the marker written next to a file which has been tagged as quarantined
+pub const QUARANTINE_TAG_SUFFIX: &str = ".quarantined";
+
+/// What oxbow should do with files which fail validation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Quarantine {
+    /// Do not validate files, commit them as they are
+    #[default]
+    Disabled,
+    /// Validate files and leave the invalid ones out of the commit, only reporting them
+    Report,
+    /// Leave invalid files out and write a `.quarantined` marker with the reason next to them
+    Tag,
+    /// Leave invalid files out and move them under `_quarantine/` in the table location
+    Move,
+}
+
+impl FromStr for Quarantine {
+    type Err = OxbowError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disabled" | "none" => Ok(Self::Disabled),
+            "report" => Ok(Self::Report),
+            "tag" => Ok(Self::Tag),
+            "move" => Ok(Self::Move),
+            other => Err(OxbowError::InvalidConfiguration(format!(
+                "Unknown quarantine mode `{other}`, expected one of: disabled, report, tag, move"
+            ))),
+        }
+    }
+}
+
+/// A file which failed validation, and why
+#[derive(Debug, Clone)]
+pub struct InvalidFile {
+    pub file: ObjectMeta,
+    pub reason: String,
+}
+
+/// The outcome of validating a set of files
+#[derive(Debug, Clone, Default)]
+pub struct Validated {
+    /// The files which can be committed
+    pub valid: Vec<ObjectMeta>,
+    /// The files which must not be committed since they are damaged, and may be quarantined
+    pub invalid: Vec<InvalidFile>,
+    /// The files which are readable but cannot be written into the table's schema, which are left
+    /// where they are
+    pub incompatible: Vec<InvalidFile>,
+}
+
+/// Why a file cannot be committed
+enum Problem {
+    /// The file is damaged
+    Invalid(String),
+    /// The file is readable but does not fit the table's schema
+    Incompatible(String),
+}
+
+/// The marker written next to a tagged file
+#[derive(Debug, Serialize)]
+struct QuarantineTag<'a> {
+    reason: &'a str,
+}
+
+/**
+ * Validate the given files, reading their footers through the [FooterCache].
+ *
+ * A file is invalid if it is empty, its footer cannot be read, its row counts do not add up, or its
+ * row groups point past the end of the file. When a `schema` is given, a file which is otherwise
+ * valid is incompatible if any of its columns has a type which cannot be written into the same
+ * column of that schema.
+ */
+pub async fn validate_files(
+    files: &[ObjectMeta],
+    footers: &FooterCache,
+    schema: Option<&Schema>,
+) -> Validated {
+    let mut result = Validated::default();
+
+    let problems = footers
+        .inspect(files, |file, metadata| match metadata {
+            _ if file.size == 0 => Some(Problem::Invalid("The file is empty".to_string())),
+            Err(err) => Some(Problem::Invalid(format!(
+                "The parquet footer cannot be read: {err}"
+            ))),
+            Ok(metadata) => match problem_with(file, metadata) {
+                Some(reason) => Some(Problem::Invalid(reason)),
+                None => schema
+                    .and_then(|schema| conflict_with(metadata, schema))
+                    .map(Problem::Incompatible),
+            },
+        })
+        .await;
+
+    for (file, problem) in files.iter().zip(problems) {
+        match problem {
+            Some(Problem::Invalid(reason)) => {
+                warn!("The file {} is not valid: {reason}", file.location);
+                result.invalid.push(InvalidFile {
+                    file: file.clone(),
+                    reason,
+                });
+            }
+            Some(Problem::Incompatible(reason)) => {
+                warn!("The file {} is not compatible: {reason}", file.location);
+                result.incompatible.push(InvalidFile {
+                    file: file.clone(),
+                    reason,
+                });
+            }
+            None => result.valid.push(file.clone()),
+        }
+    }
+    result
+}
+
+/**
+ * Return a description of what is wrong with the file, if anything
+ */
+fn problem_with(file: &ObjectMeta, metadata: &ParquetMetaData) -> Option<String> {
+    // Jobs write footers without any rows for empty tasks, those are valid files
+    let num_rows = metadata.file_metadata().num_rows();
+    if num_rows < 0 {
+        return Some(format!("The footer declares {num_rows} rows"));
+    }
+
+    let row_group_rows: i64 = metadata.row_groups().iter().map(|rg| rg.num_rows()).sum();
+    if row_group_rows != num_rows {
+        return Some(format!(
+            "The footer declares {num_rows} rows but the row groups contain {row_group_rows}"
+        ));
+    }
+
+    for rg in metadata.row_groups() {
+        for column in rg.columns() {
+            let (start, length) = column.byte_range();
+            if start + length > file.size as u64 {
+                return Some(format!(
+                    "The column `{}` extends past the end of the file, it may be truncated",
+                    column.column_path()
+                ));
+            }
+        }
+    }
+    None
+}
+
+/**
+ * Return a description of the first column of the file which cannot be written into the schema,
+ * if there is one
+ */
+fn conflict_with(metadata: &ParquetMetaData, schema: &Schema) -> Option<String> {
+    let file_schema = match file_schema_from(metadata) {
+        Ok(file_schema) => file_schema,
+        Err(err) => return Some(format!("The schema cannot be represented in Delta: {err}")),
+    };
+    file_schema.get_fields().iter().find_map(|field| {
+        let existing = schema.get_field_with_name(field.get_name()).ok()?;
+        match is_writable(existing.get_type(), field.get_type()) {
+            true => None,
+            false => Some(format!(
+                "The column `{}` has the type {:?} which cannot be written as {:?}",
+                field.get_name(),
+                field.get_type(),
+                existing.get_type()
+            )),
+        }
+    })
+}
+
+fn file_schema_from(metadata: &ParquetMetaData) -> OxbowResult<Schema> {
+    let arrow_schema = crate::footers::schema_from(metadata)?;
+    let (arrow_schema, _) = crate::types::delta_compatible_schema(&arrow_schema)?;
+    Ok(Schema::try_from(&arrow_schema)?)
+}
+
+/**
+ * Return true if values of the file's type can be read as the table's type
+ */
+fn is_writable(table: &SchemaDataType, file: &SchemaDataType) -> bool {
+    match (table, file) {
+        (SchemaDataType::primitive(table), SchemaDataType::primitive(file)) => {
+            table == file
+                || matches!(
+                    (table.as_str(), file.as_str()),
+                    ("short", "byte")
+                        | ("integer", "byte" | "short")
+                        | ("long", "byte" | "short" | "integer")
+                        | ("double", "float")
+                )
+        }
+        (table, file) => table == file,
+    }
+}
+
+/**
+ * Quarantine the invalid files according to the [Quarantine] mode.
+ *
+ * Failures are logged rather than returned, since the invalid files are left out of the commit
+ * whether or not they could be quarantined.
+ */
+pub async fn quarantine(invalid: &[InvalidFile], mode: Quarantine, store: Arc<DeltaObjectStore>) {
+    for bad in invalid.iter() {
+        let location = &bad.file.location;
+        let result = match mode {
+            Quarantine::Disabled | Quarantine::Report => Ok(()),
+            Quarantine::Tag => {
+                let tag = serde_json::to_vec(&QuarantineTag {
+                    reason: &bad.reason,
+                })
+                .unwrap_or_default();
+                store
+                    .put(&tag_location(location), tag.into())
+                    .await
+                    .map(|_| ())
+            }
+            Quarantine::Move => store.rename(location, &quarantine_location(location)).await,
+        };
+
+        match result {
+            Ok(_) if mode == Quarantine::Report => {}
+            Ok(_) => info!("Quarantined {location}: {}", bad.reason),
+            Err(err) => error!("Failed to quarantine {location}: {err:?}"),
+        }
+    }
+}
+
+/**
+ * Return the location a quarantined file is moved to
+ */
+pub fn quarantine_location(location: &Path) -> Path {
+    Path::from(format!("{QUARANTINE_PREFIX}/{location}"))
+}
+
+/**
+ * Return the location of the marker written next to a tagged file
+ */
+pub fn tag_location(location: &Path) -> Path {
+    Path::from(format!("{location}{QUARANTINE_TAG_SUFFIX}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_quarantine_modes() {
+        assert_eq!(Quarantine::Disabled, "none".parse::<Quarantine>().unwrap());
+        assert_eq!(Quarantine::Report, "report".parse::<Quarantine>().unwrap());
+        assert_eq!(Quarantine::Tag, "Tag".parse::<Quarantine>().unwrap());
+        assert_eq!(Quarantine::Move, "move".parse::<Quarantine>().unwrap());
+        assert!("delete".parse::<Quarantine>().is_err());
+    }
+
+    #[test]
+    fn writable_types() {
+        let p = |name: &str| SchemaDataType::primitive(name.into());
+        assert!(is_writable(&p("long"), &p("integer")));
+        assert!(is_writable(&p("double"), &p("float")));
+        assert!(is_writable(&p("string"), &p("string")));
+        assert!(!is_writable(&p("integer"), &p("long")));
+        assert!(!is_writable(&p("string"), &p("integer")));
+    }
+
+    #[test]
+    fn quarantine_locations() {
+        let location = Path::from("ds=1/a.parquet");
+        assert_eq!(
+            Path::from("_quarantine/ds=1/a.parquet"),
+            quarantine_location(&location)
+        );
+        assert_eq!(
+            Path::from("ds=1/a.parquet.quarantined"),
+            tag_location(&location)
+        );
+        assert!(crate::spark::is_hidden(&quarantine_location(&location)));
+    }
+
+    #[tokio::test]
+    async fn validate_bad_files() {
+        let (tempdir, store) = crate::tests::util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-non-partitioned",
+        );
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let good = files[0].clone();
+        let root = tempdir.path().join("deltatbl-non-partitioned");
+
+        std::fs::write(root.join("empty.parquet"), "").unwrap();
+        let contents = std::fs::read(tempdir.path().join(good.location.as_ref())).unwrap();
+        std::fs::write(
+            root.join("truncated.parquet"),
+            &contents[contents.len() / 2..],
+        )
+        .unwrap();
+
+        let candidates = vec![
+            good.clone(),
+            store
+                .head(&Path::from("deltatbl-non-partitioned/empty.parquet"))
+                .await
+                .unwrap(),
+            store
+                .head(&Path::from("deltatbl-non-partitioned/truncated.parquet"))
+                .await
+                .unwrap(),
+        ];
+
+        let footers = FooterCache::new(store.clone(), 4, Default::default());
+        let validated = validate_files(&candidates, &footers, None).await;
+        assert_eq!(
+            vec![good.location.clone()],
+            validated
+                .valid
+                .iter()
+                .map(|f| f.location.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, validated.invalid.len());
+
+        quarantine(&validated.invalid, Quarantine::Move, store.clone()).await;
+        assert!(store
+            .head(&Path::from(
+                "_quarantine/deltatbl-non-partitioned/empty.parquet"
+            ))
+            .await
+            .is_ok());
+        assert!(store
+            .head(&Path::from("deltatbl-non-partitioned/empty.parquet"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn validate_files_without_rows() {
+        use deltalake::arrow::array::Int32Array;
+        use deltalake::arrow::datatypes::{DataType, Field, Schema as ArrowSchema};
+        use deltalake::arrow::record_batch::RecordBatch;
+
+        let (tempdir, store) = crate::tests::util::create_empty_temp_path();
+        let schema = Arc::new(ArrowSchema::new(vec![Field::new(
+            "id",
+            DataType::Int32,
+            true,
+        )]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int32Array::from(Vec::<i32>::new()))])
+                .unwrap();
+        crate::tests::util::write_parquet(tempdir.path(), "part-00000.parquet", &batch);
+
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        assert_eq!(1, files.len());
+
+        let footers = FooterCache::new(store.clone(), 4, Default::default());
+        let validated = validate_files(&files, &footers, None).await;
+        assert_eq!(1, validated.valid.len(), "{:?}", validated.invalid);
+    }
+
+    #[tokio::test]
+    async fn validate_against_schema() {
+        let (_tempdir, store) = crate::tests::util::create_temp_path_with(
+            "../../tests/data/hive/deltatbl-non-partitioned",
+        );
+        let files = crate::discover_parquet_files(store.clone())
+            .await
+            .expect("Failed to discover parquet files");
+        let footers = FooterCache::new(store.clone(), 4, Default::default());
+
+        let footer = footers.footer(&files[0]).await.unwrap();
+        let (arrow_schema, _) = crate::types::delta_compatible_schema(&footer.schema).unwrap();
+        let schema = Schema::try_from(&arrow_schema).unwrap();
+        let validated = validate_files(&files, &footers, Some(&schema)).await;
+        assert!(validated.invalid.is_empty());
+
+        // Pretend every column of the table is a boolean
+        let booleans = Schema::new(
+            schema
+                .get_fields()
+                .iter()
+                .map(|f| {
+                    deltalake::SchemaField::new(
+                        f.get_name().to_string(),
+                        SchemaDataType::primitive("boolean".into()),
+                        true,
+                        Default::default(),
+                    )
+                })
+                .collect(),
+        );
+        let validated = validate_files(&files, &footers, Some(&booleans)).await;
+        assert!(validated.invalid.is_empty());
+        assert_eq!(files.len(), validated.incompatible.len());
+    }
+}
@@ -93,9 +93,17 @@ fn segmented_by_prefix(
 ) -> Result<HashMap<String, Vec<S3EventRecord>>, Error> {
     let mut segments = HashMap::new();
 
+    let layout: oxbow::partitions::PartitionLayout = match std::env::var("PARTITION_TEMPLATE") {
+        Ok(template) => template.parse()?,
+        Err(_) => Default::default(),
+    };
+
     for record in records_with_url_decoded_keys(records) {
         if let Some(bucket) = &record.s3.bucket.name {
-            let log_path = infer_log_path_from(record.s3.object.url_decoded_key.as_ref().unwrap());
+            let log_path = infer_log_path_with(
+                record.s3.object.url_decoded_key.as_ref().unwrap(),
+                layout.depth(),
+            );
             let key = format!("s3://{}/{}", bucket, log_path);
 
             if !segments.contains_key(&key) {
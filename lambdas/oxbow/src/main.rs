@@ -3,13 +3,14 @@
  */
 
 use aws_lambda_events::sqs::SqsEvent;
-use deltalake::DeltaTableError;
+use deltalake::{DeltaTableError, ObjectMeta, ObjectStore, ObjectStoreError};
 use dynamodb_lock::Region;
 use lambda_runtime::{service_fn, Error, LambdaEvent};
 use serde_json::Value;
 use tracing::log::*;
 use url::Url;
 
+use oxbow::transactions::Transaction;
 use oxbow_lambda_shared::*;
 
 use std::collections::HashMap;
@@ -32,12 +33,17 @@ async fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
-async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
+async fn func(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
     debug!("Receiving event: {:?}", event);
-    let records = s3_from_sqs(event.payload)?;
-    debug!("processing records: {records:?}");
-    let records = records_with_url_decoded_keys(&records);
-    let by_table = objects_by_table(&records);
+    let request_id = event.context.request_id.clone();
+    let options = conversion_options()?;
+    let versioned_deletes = match std::env::var("VERSIONED_DELETES") {
+        Ok(mode) => mode.parse()?,
+        Err(_) => Default::default(),
+    };
+    let removals =
+        RemovalEvents::new(&env_list("REMOVAL_EVENTS")).with_versioned_deletes(versioned_deletes);
+    let by_table = eligible_objects_by_table(&event.payload, &options, &removals)?;
 
     if by_table.is_empty() {
         info!("No elligible events found, exiting early");
@@ -49,9 +55,17 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
     for table_name in by_table.keys() {
         let location = Url::parse(table_name).expect("Failed to turn a table into a URL");
         debug!("Handling table: {:?}", location);
-        let table_mods = by_table
+        let batches = by_table
             .get(table_name)
             .expect("Failed to get the files for a table, impossible!");
+        let message_ids: Vec<String> = batches
+            .iter()
+            .flat_map(|mods| mods.message_ids.iter().cloned())
+            .collect();
+        let mut options = options.clone();
+        options
+            .commit_metadata
+            .extend(commit_lineage(&location, &message_ids, &request_id));
         let mut storage_options: HashMap<String, String> = HashMap::default();
         // Ensure that the DeltaTable we get back uses the table-name as a partition key
         // when locking in DynamoDb: <https://github.com/buoyant-data/oxbow/issues/9>
@@ -75,29 +89,56 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
         {
             Ok(mut table) => {
                 info!("Opened table to append: {:?}", table);
+                let batches = match verify_removes(batches, table.object_store().as_ref()).await {
+                    Ok(batches) => batches,
+                    Err(err) => {
+                        error!("Failed to check the removed objects of {location}: {err:?}");
+                        let _ = release_lock(lock, &lock_client).await;
+                        return Err(Box::new(err));
+                    }
+                };
+                let batches = batches.as_slice();
+                let adds = pending(
+                    batches,
+                    |mods| (&mods.adds, mods.add_sequence),
+                    Transaction::for_adds,
+                    |txn| txn.is_applied(&table),
+                );
+                let removes = pending(
+                    batches,
+                    |mods| (&mods.removes, mods.remove_sequence),
+                    Transaction::for_removes,
+                    |txn| txn.is_applied(&table),
+                );
+                let store = table.object_store();
+                let late = async {
+                    Ok::<_, ObjectStoreError>((
+                        unapplied(&adds.covered, true, store.as_ref()).await?,
+                        unapplied(&removes.covered, false, store.as_ref()).await?,
+                    ))
+                };
+                let (late_adds, late_removes) = match late.await {
+                    Ok(late) => late,
+                    Err(err) => {
+                        error!("Failed to check the delayed batches of {location}: {err:?}");
+                        let _ = release_lock(lock, &lock_client).await;
+                        return Err(Box::new(err));
+                    }
+                };
+                options.transaction = adds.transaction;
+                let remove_transaction = removes.transaction;
+                let adds: Vec<ObjectMeta> = adds.files.into_iter().chain(late_adds).collect();
+                let removes: Vec<ObjectMeta> =
+                    removes.files.into_iter().chain(late_removes).collect();
 
-                match oxbow::append_to_table(table_mods.adds.as_slice(), &mut table).await {
+                match oxbow::append_to_table_with_options(adds.as_slice(), &mut table, &options)
+                    .await
+                {
                     Ok(version) => {
                         info!(
                             "Successfully appended version {} to table at {}",
                             version, location
                         );
-
-                        if version % 10 == 0 {
-                            info!("Creating a checkpoint for {}", location);
-                            debug!("Reloading the table state to get the latest version");
-                            let _ = table.load().await;
-                            if table.version() == version {
-                                match deltalake::checkpoints::create_checkpoint(&table).await {
-                                    Ok(_) => info!("Successfully created checkpoint"),
-                                    Err(e) => {
-                                        error!("Failed to create checkpoint for {location}: {e:?}")
-                                    }
-                                }
-                            } else {
-                                error!("The table was reloaded to create a checkpoint but a new version already exists!");
-                            }
-                        }
                     }
                     Err(err) => {
                         error!("Failed to append to the table {}: {:?}", location, err);
@@ -106,12 +147,20 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
                     }
                 }
 
-                if !table_mods.removes.is_empty() {
+                if !removes.is_empty() {
                     info!(
                         "{} Remove actions are expected in this operation",
-                        table_mods.removes.len()
+                        removes.len()
                     );
-                    match oxbow::remove_from_table(table_mods.removes.as_slice(), &mut table).await
+                    // Removes for files the table never tracked, such as originals deleted after a
+                    // timestamp rewrite, are skipped
+                    options.transaction = remove_transaction;
+                    match oxbow::remove_from_table_with_options(
+                        removes.as_slice(),
+                        &mut table,
+                        &options,
+                    )
+                    .await
                     {
                         Ok(version) => {
                             info!(
@@ -119,6 +168,10 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
                                 version
                             );
                         }
+                        Err(err @ oxbow::error::OxbowError::AppendOnlyTable { .. }) => {
+                            // Retrying the batch cannot succeed, so the removals are dropped
+                            error!("Ignoring the removals for {location}: {err}");
+                        }
                         Err(err) => {
                             error!(
                                 "Failed to create removes on the table {}: {:?}",
@@ -133,7 +186,8 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
             Err(DeltaTableError::NotATable(_e)) => {
                 // create the table with our objects
                 info!("Creating new Delta table at: {location}");
-                let table = oxbow::convert(table_name, Some(storage_options)).await;
+                let table =
+                    oxbow::convert_with_options(table_name, Some(storage_options), &options).await;
                 info!("Created table at: {location}");
 
                 if table.is_err() {
@@ -155,6 +209,245 @@ async fn func<'a>(event: LambdaEvent<SqsEvent>) -> Result<Value, Error> {
     Ok("[]".into())
 }
 
+/**
+ * Build the [oxbow::ConversionOptions] for this function from its environment, the README's
+ * Lambda configuration table describes each variable and its default
+ *
+ * - `SCHEMA_EVOLUTION`: add new columns from appended files to the table schema
+ * - `TIMESTAMP_REWRITE`: `keep` or `remove` to rewrite files with non-microsecond timestamps
+ * - `MAX_FILES_PER_COMMIT`: largest number of files to add in a commit when creating a table
+ * - `CHECKPOINT_AFTER_CONVERSION`: checkpoint a table once it has been created
+ * - `CHECKPOINT_INTERVAL`: commits between checkpoints without `delta.checkpointInterval`
+ * - `EXPIRED_LOG_CLEANUP`: remove expired log files after each checkpoint
+ * - `FOOTER_CONCURRENCY`: number of parquet footers to read at the same time
+ * - `PARTITION_TEMPLATE`: path template of the partition directories instead of hive-style
+ * - `INFER_PARTITION_TYPES`: infer the types of partition columns when creating a table
+ * - `INCLUDE_PATTERNS` and `EXCLUDE_PATTERNS`: comma separated globs of files to consider or ignore
+ * - `PARQUET_EXTENSIONS`: comma separated extensions of parquet files
+ * - `SNIFF_PARQUET`: check the magic bytes of files without a parquet extension
+ * - `QUARANTINE`: `report`, `tag` or `move` to validate files and leave invalid ones out
+ * - `TABLE_PROPERTIES`: comma separated `KEY=VALUE` properties to create tables with
+ * - `TABLE_NAME` and `TABLE_DESCRIPTION`: the name and description to create tables with
+ */
+fn conversion_options() -> Result<oxbow::ConversionOptions, Error> {
+    let timestamp_rewrite = match std::env::var("TIMESTAMP_REWRITE") {
+        Ok(mode) => mode.parse()?,
+        Err(_) => Default::default(),
+    };
+
+    let quarantine = match std::env::var("QUARANTINE") {
+        Ok(mode) => mode.parse()?,
+        Err(_) => Default::default(),
+    };
+    let max_files_per_commit = match std::env::var("MAX_FILES_PER_COMMIT") {
+        Ok(count) => Some(count.parse()?),
+        Err(_) => None,
+    };
+    let checkpoint_interval = match std::env::var("CHECKPOINT_INTERVAL") {
+        Ok(count) => Some(count.parse()?),
+        Err(_) => None,
+    };
+    let footer_concurrency = match std::env::var("FOOTER_CONCURRENCY") {
+        Ok(count) => Some(count.parse()?),
+        Err(_) => None,
+    };
+    let partition_layout = match std::env::var("PARTITION_TEMPLATE") {
+        Ok(template) => template.parse()?,
+        Err(_) => Default::default(),
+    };
+
+    Ok(oxbow::ConversionOptions {
+        schema_evolution: env_flag("SCHEMA_EVOLUTION"),
+        timestamp_rewrite,
+        max_files_per_commit,
+        checkpoint_after_conversion: env_flag("CHECKPOINT_AFTER_CONVERSION"),
+        checkpoint_interval,
+        expired_log_cleanup: env_flag("EXPIRED_LOG_CLEANUP"),
+        footer_concurrency,
+        partition_layout,
+        infer_partition_types: env_flag("INFER_PARTITION_TYPES"),
+        include: env_list("INCLUDE_PATTERNS"),
+        exclude: env_list("EXCLUDE_PATTERNS"),
+        parquet_extensions: env_list("PARQUET_EXTENSIONS"),
+        sniff_content: env_flag("SNIFF_PARQUET"),
+        quarantine,
+        table_properties: oxbow::properties::parse_pairs(&env_list("TABLE_PROPERTIES"))?,
+        table_name: std::env::var("TABLE_NAME").ok(),
+        table_description: std::env::var("TABLE_DESCRIPTION").ok(),
+        ..Default::default()
+    })
+}
+
+/**
+ * Group the objects of the SQS event by their tables, leaving out those which the conversion would
+ * not pick up from the table's location: hidden files such as those written to `_quarantine/` or
+ * `_temporary/`, files excluded by the path filters, and files outside the partition template
+ */
+fn eligible_objects_by_table(
+    event: &SqsEvent,
+    options: &oxbow::ConversionOptions,
+    removals: &RemovalEvents,
+) -> Result<HashMap<String, Vec<TableMods>>, Error> {
+    let filter = options.path_filter()?;
+    let by_table =
+        objects_by_table_from_sqs(event, options.partition_layout.depth(), removals, |path| {
+            if oxbow::spark::is_hidden(path) || !filter.matches(path) {
+                return false;
+            }
+            let matches = options.partition_layout.matches(path.as_ref());
+            if !matches {
+                warn!("Ignoring {path} which does not match the partition template");
+            }
+            matches
+        })?;
+    Ok(by_table)
+}
+
+/**
+ * Return the batches with each of their [TableMods::unverified_removes] which no longer exists in
+ * the store moved into their removes, and those which still exist dropped
+ */
+async fn verify_removes(
+    batches: &[TableMods],
+    store: &dyn ObjectStore,
+) -> Result<Vec<TableMods>, ObjectStoreError> {
+    let mut verified = vec![];
+    for batch in batches.iter() {
+        let mut batch = batch.clone();
+        for object in std::mem::take(&mut batch.unverified_removes) {
+            match store.head(&object.location).await {
+                Ok(_) => debug!(
+                    "Not removing {} since a version of it still exists",
+                    object.location
+                ),
+                Err(ObjectStoreError::NotFound { .. }) => batch.removes.push(object),
+                Err(err) => return Err(err),
+            }
+        }
+        verified.push(batch);
+    }
+    Ok(verified)
+}
+
+/// The files of the batches of events, one for each SQS message, which are to be committed
+#[derive(Debug, Default)]
+struct Pending {
+    /// The files of the batches which the table has not applied yet
+    files: Vec<ObjectMeta>,
+    /// The transaction to commit those files with, whose version is their latest sequence
+    transaction: Option<Transaction>,
+    /// The files of the batches whose sequence the table's transaction has already reached
+    covered: Vec<ObjectMeta>,
+}
+
+/**
+ * Split the files of the batches of events, one for each SQS message, of the kind `files` selects
+ * along with their sequence, by whether the table has applied them.
+ *
+ * A batch is applied once the table's transaction made by `transaction` has reached its sequence.
+ * SQS delivers messages in no particular order though, so a batch delayed behind a later one is
+ * covered by the table's transaction without having been applied. The files of those batches are
+ * kept apart to be checked with [unapplied].
+ */
+fn pending<F>(
+    batches: &[TableMods],
+    files: fn(&TableMods) -> (&Vec<ObjectMeta>, Option<i64>),
+    transaction: fn(i64) -> Transaction,
+    is_applied: F,
+) -> Pending
+where
+    F: Fn(&Transaction) -> bool,
+{
+    let mut pending = Pending::default();
+    let mut latest = None;
+    for (batch_files, sequence) in batches.iter().map(files) {
+        if batch_files.is_empty() {
+            continue;
+        }
+        let covered = sequence
+            .map(|sequence| is_applied(&transaction(sequence)))
+            .unwrap_or(false);
+        if covered {
+            pending.covered.extend(batch_files.iter().cloned());
+        } else {
+            pending.files.extend(batch_files.iter().cloned());
+            latest = latest.max(sequence);
+        }
+    }
+    pending.transaction = latest.map(transaction);
+    pending
+}
+
+/**
+ * Return the files of covered batches, see [Pending::covered], which may not have been applied:
+ * the `added` files which still exist, or the removed files which no longer exist.
+ *
+ * A redelivered batch therefore cannot add a file which has since been removed again, or remove
+ * one which has since been added again. Files which the table already tracks, or does not track
+ * for removals, are skipped when committing.
+ */
+async fn unapplied(
+    files: &[ObjectMeta],
+    added: bool,
+    store: &dyn ObjectStore,
+) -> Result<Vec<ObjectMeta>, ObjectStoreError> {
+    let mut unapplied = vec![];
+    for file in files.iter() {
+        let exists = match store.head(&file.location).await {
+            Ok(_) => true,
+            Err(ObjectStoreError::NotFound { .. }) => false,
+            Err(err) => return Err(err),
+        };
+        if exists == added {
+            unapplied.push(file.clone());
+        } else {
+            debug!("Skipping {} whose batch was already applied", file.location);
+        }
+    }
+    Ok(unapplied)
+}
+
+/**
+ * Return the commitInfo keys describing what triggered this invocation's commits to the table
+ */
+fn commit_lineage(
+    location: &Url,
+    message_ids: &[String],
+    request_id: &str,
+) -> serde_json::Map<String, Value> {
+    let mut lineage = serde_json::Map::new();
+    if let Some(bucket) = location.host_str() {
+        lineage.insert("sourceBucket".into(), Value::from(bucket));
+    }
+    lineage.insert("sqsMessageIds".into(), Value::from(message_ids.to_vec()));
+    lineage.insert("lambdaRequestId".into(), Value::from(request_id));
+    lineage
+}
+
+/**
+ * Return the comma separated values of the given environment variable, if it is set
+ */
+fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|v| {
+            v.split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * Return true if the given environment variable is set to a truthy value
+ */
+fn env_flag(name: &str) -> bool {
+    matches!(
+        std::env::var(name).map(|v| v.to_lowercase()).as_deref(),
+        Ok("true") | Ok("1") | Ok("yes")
+    )
+}
+
 /**
  * Simple helper function to acquire a lock with DynamoDb
  *
@@ -195,4 +488,194 @@ async fn release_lock(
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_flag() {
+        std::env::set_var("OXBOW_TEST_FLAG_TRUE", "True");
+        std::env::set_var("OXBOW_TEST_FLAG_FALSE", "false");
+        assert!(env_flag("OXBOW_TEST_FLAG_TRUE"));
+        assert!(!env_flag("OXBOW_TEST_FLAG_FALSE"));
+        assert!(!env_flag("OXBOW_TEST_FLAG_UNSET"));
+    }
+
+    #[test]
+    fn test_commit_lineage() {
+        let location = Url::parse("s3://my-bucket/tables/events").unwrap();
+        let lineage = commit_lineage(&location, &["msg-1".to_string()], "req-1");
+        assert_eq!(Some(&Value::from("my-bucket")), lineage.get("sourceBucket"));
+        assert_eq!(
+            Some(&Value::from(vec!["msg-1"])),
+            lineage.get("sqsMessageIds")
+        );
+        assert_eq!(Some(&Value::from("req-1")), lineage.get("lambdaRequestId"));
+    }
+
+    fn object(path: &str) -> ObjectMeta {
+        ObjectMeta {
+            location: path.into(),
+            last_modified: Default::default(),
+            size: 0,
+            e_tag: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_removes() {
+        let store = deltalake::storage::DeltaObjectStore::try_new(
+            Url::parse("memory://").unwrap(),
+            HashMap::<String, String>::new(),
+        )
+        .expect("Failed to create the store");
+        store
+            .put(&"noncurrent.parquet".into(), "PAR1".into())
+            .await
+            .expect("Failed to write the object");
+
+        // Deleting the only version of an object leaves nothing behind, unlike a noncurrent one
+        let batches = vec![TableMods {
+            removes: vec![object("marked.parquet")],
+            unverified_removes: vec![object("only-version.parquet"), object("noncurrent.parquet")],
+            ..Default::default()
+        }];
+        let verified = verify_removes(&batches, &store)
+            .await
+            .expect("Failed to verify the removes");
+        assert_eq!(
+            vec![
+                deltalake::Path::from("marked.parquet"),
+                deltalake::Path::from("only-version.parquet")
+            ],
+            verified[0]
+                .removes
+                .iter()
+                .map(|o| o.location.clone())
+                .collect::<Vec<_>>()
+        );
+        assert!(verified[0].unverified_removes.is_empty());
+    }
+
+    #[test]
+    fn test_pending_batches() {
+        let batch = |sequence: i64, adds: &[&str], removes: &[&str]| TableMods {
+            adds: adds.iter().map(|path| object(path)).collect(),
+            removes: removes.iter().map(|path| object(path)).collect(),
+            add_sequence: (!adds.is_empty()).then_some(sequence),
+            remove_sequence: (!removes.is_empty()).then_some(sequence),
+            ..Default::default()
+        };
+        // The table has applied the added files up to the sequence 5, and the message with the
+        // sequence 3 arrives after that
+        let batches = vec![
+            batch(5, &["a.parquet"], &["b.parquet"]),
+            batch(3, &["c.parquet"], &[]),
+            batch(7, &["d.parquet"], &[]),
+            batch(6, &[], &["a.parquet"]),
+        ];
+        let is_applied =
+            |txn: &Transaction| txn.app_id == oxbow::transactions::ADD_APP_ID && txn.version <= 5;
+        let locations = |files: &[ObjectMeta]| {
+            files
+                .iter()
+                .map(|f| f.location.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let adds = pending(
+            &batches,
+            |mods| (&mods.adds, mods.add_sequence),
+            Transaction::for_adds,
+            is_applied,
+        );
+        assert_eq!(vec!["d.parquet"], locations(&adds.files));
+        assert_eq!(vec!["a.parquet", "c.parquet"], locations(&adds.covered));
+        assert_eq!(Some(Transaction::for_adds(7)), adds.transaction);
+
+        let removes = pending(
+            &batches,
+            |mods| (&mods.removes, mods.remove_sequence),
+            Transaction::for_removes,
+            is_applied,
+        );
+        assert_eq!(vec!["b.parquet", "a.parquet"], locations(&removes.files));
+        assert!(removes.covered.is_empty());
+        assert_eq!(Some(Transaction::for_removes(6)), removes.transaction);
+    }
+
+    #[tokio::test]
+    async fn test_unapplied_files() {
+        let store = deltalake::storage::DeltaObjectStore::try_new(
+            Url::parse("memory://").unwrap(),
+            HashMap::<String, String>::new(),
+        )
+        .expect("Failed to create the store");
+        store
+            .put(&"delayed.parquet".into(), "PAR1".into())
+            .await
+            .expect("Failed to write the object");
+
+        // The redelivered file has been removed since it was added
+        let covered = vec![object("redelivered.parquet"), object("delayed.parquet")];
+        let added = unapplied(&covered, true, &store)
+            .await
+            .expect("Failed to check the files");
+        assert_eq!(
+            vec![deltalake::Path::from("delayed.parquet")],
+            added.into_iter().map(|f| f.location).collect::<Vec<_>>()
+        );
+        let removed = unapplied(&covered, false, &store)
+            .await
+            .expect("Failed to check the files");
+        assert_eq!(
+            vec![deltalake::Path::from("redelivered.parquet")],
+            removed.into_iter().map(|f| f.location).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_hidden_objects_are_not_tables() {
+        let body = |key: &str| {
+            format!(
+                r#"{{"Records":[{{"eventVersion":"2.1","eventSource":"aws:s3","awsRegion":"us-west-2","eventTime":"2023-12-18T00:22:24.292Z","eventName":"ObjectCreated:Copy","userIdentity":{{"principalId":"A16S3A764ZBGJN"}},"requestParameters":{{"sourceIPAddress":"76.218.225.124"}},"responseElements":{{}},"s3":{{"s3SchemaVersion":"1.0","configurationId":"test","bucket":{{"name":"oxbow-simple","ownerIdentity":{{"principalId":"A16S3A764ZBGJN"}},"arn":"arn:aws:s3:::oxbow-simple"}},"object":{{"key":"{key}","size":10,"sequencer":"00657F90C047858AE9"}}}}}}]}}"#
+            )
+        };
+        let event = SqsEvent {
+            records: [
+                "tables/events/_quarantine/part-0.parquet",
+                "tables/events/_temporary/0/part-1.parquet",
+                "tables/events/.spark-staging-1/part-2.parquet",
+            ]
+            .iter()
+            .map(|key| aws_lambda_events::sqs::SqsMessage {
+                message_id: Some(key.to_string()),
+                body: Some(body(key)),
+                ..Default::default()
+            })
+            .collect(),
+        };
+
+        // A quarantined file must not be converted as a table of its own, or it would be
+        // quarantined again beneath that table over and over
+        let by_table = eligible_objects_by_table(
+            &event,
+            &oxbow::ConversionOptions::default(),
+            &RemovalEvents::default(),
+        )
+        .expect("Failed to group the objects");
+        assert!(
+            by_table.is_empty(),
+            "Hidden objects were grouped: {by_table:?}"
+        );
+    }
+
+    #[test]
+    fn test_env_list() {
+        std::env::set_var("OXBOW_TEST_LIST", "tmp/**, backup/**,");
+        assert_eq!(
+            vec!["tmp/**".to_string(), "backup/**".to_string()],
+            env_list("OXBOW_TEST_LIST")
+        );
+        assert!(env_list("OXBOW_TEST_LIST_UNSET").is_empty());
+    }
+}